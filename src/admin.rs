@@ -1,7 +1,12 @@
 use std::sync::Arc;
 
 use crate::xds::XdsStore;
-use axum::{Router, extract::State, http::StatusCode, routing::get};
+use axum::{
+	Router,
+	extract::{Path, State},
+	http::StatusCode,
+	routing::get,
+};
 use tracing::error;
 #[derive(Clone)]
 pub struct App {
@@ -16,6 +21,9 @@ impl App {
 		Router::new()
 			.route("/targets", get(targets_handler))
 			.route("/rbac", get(rbac_handler))
+			.route("/upstreams", get(upstreams_handler))
+			.route("/upstreams/{name}/stderr", get(stderr_handler))
+			.route("/upstreams/{name}/endpoints", get(endpoints_handler))
 			.route("/listeners", get(listener_handler))
 			.with_state(self.clone())
 	}
@@ -43,6 +51,51 @@ async fn rbac_handler(State(app): State<App>) -> Result<String, StatusCode> {
 	}
 }
 
+async fn upstreams_handler(State(app): State<App>) -> Result<String, StatusCode> {
+	let upstreams = app.state.read().unwrap().upstreams.clone();
+	match serde_json::to_string(&upstreams) {
+		Ok(json_upstreams) => Ok(json_upstreams),
+		Err(e) => {
+			error!("error serializing upstreams: {:?}", e);
+			Err(StatusCode::INTERNAL_SERVER_ERROR)
+		},
+	}
+}
+
+async fn stderr_handler(
+	State(app): State<App>,
+	Path(name): Path<String>,
+) -> Result<String, StatusCode> {
+	let lines = app
+		.state
+		.read()
+		.unwrap()
+		.upstreams
+		.stderr_lines(&name)
+		.ok_or(StatusCode::NOT_FOUND)?;
+	Ok(lines.join("\n"))
+}
+
+async fn endpoints_handler(
+	State(app): State<App>,
+	Path(name): Path<String>,
+) -> Result<String, StatusCode> {
+	let endpoints = app
+		.state
+		.read()
+		.unwrap()
+		.upstreams
+		.endpoints(&name)
+		.ok_or(StatusCode::NOT_FOUND)?;
+	match serde_json::to_string(&endpoints) {
+		Ok(json_endpoints) => Ok(json_endpoints),
+		Err(e) => {
+			error!("error serializing endpoints: {:?}", e);
+			Err(StatusCode::INTERNAL_SERVER_ERROR)
+		},
+	}
+}
+
 async fn listener_handler(State(app): State<App>) -> Result<String, StatusCode> {
 	let listener = app.state.read().unwrap().listener.clone();
 	match serde_json::to_string(&listener) {
@@ -19,9 +19,91 @@ pub enum Listener {
 		port: u32,
 		mode: Option<ListenerMode>,
 		authn: Option<Authn>,
+		options: Option<RelayOptions>,
 	},
 	#[serde(rename = "stdio")]
-	Stdio {},
+	Stdio { options: Option<RelayOptions> },
+}
+
+/// RelayOptions configures how the targets behind a listener are presented to clients.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct RelayOptions {
+	/// If set, clients are sent a warning log message naming the targets missing from a list response.
+	#[serde(default)]
+	pub warn_unavailable_targets: bool,
+	/// If set, every page of each target is listed, and the merged list is paged by the proxy.
+	/// Otherwise, the proxy returns one page of each target at a time.
+	/// Tools and prompts are always drained when they are not always prefixed,
+	/// as name conflicts can only be found over every page.
+	#[serde(default)]
+	pub drain_pages: bool,
+	/// The size of the pages of the merged list when pages are drained. If unset, the whole list is returned.
+	pub page_size: Option<usize>,
+	/// The scheme of the URIs resources are listed under, `<scheme>://<target>/<upstream uri>`.
+	/// Defaults to mcp-proxy.
+	pub resource_scheme: Option<String>,
+	/// The separator between the target and the name of tools and prompts. Defaults to `:`.
+	pub separator: Option<String>,
+	/// When tools and prompts are prefixed with their target. Defaults to always.
+	pub prefix: Option<PrefixStrategy>,
+	/// The maximum length of tool and prompt names. Longer names are shortened, ending in a hash.
+	/// It must leave room for the hash, `_` and 8 hex digits, so it must be at least 10.
+	#[serde(default, deserialize_with = "deserialize_max_name_length")]
+	pub max_name_length: Option<usize>,
+	/// If set, tool and prompt names are made to match `^[a-zA-Z0-9_-]{1,64}$`, which some clients require.
+	/// Other characters, as `.`, `/` or spaces, are replaced by `_` and the name ends in a hash,
+	/// so it stays unique and maps back to the original name. This includes the separator,
+	/// so the default `:` becomes `_`. Names are shortened to 64 characters unless max_name_length is lower.
+	#[serde(default)]
+	pub sanitize_names: bool,
+	/// If set, tool call arguments are validated against the input schema the tool was listed with,
+	/// and invalid calls are rejected without reaching the target.
+	#[serde(default)]
+	pub validate_arguments: bool,
+	/// The server name reported to clients. Defaults to the name of the proxy.
+	pub server_name: Option<String>,
+	/// The server version reported to clients. Defaults to the version of the proxy.
+	pub server_version: Option<String>,
+	/// The instructions sent to clients when they initialize.
+	pub instructions: Option<String>,
+	/// If set, the instructions are followed by those of each target, as of the last connection to it.
+	/// The proxy connects to each target when it is inserted or updated to fetch them,
+	/// so only targets it failed to connect to are left out.
+	#[serde(default)]
+	pub compose_instructions: bool,
+	/// If set, clients are only listed the search_tools and invoke_tool meta-tools,
+	/// to find and call the tools of the targets without listing all of them.
+	#[serde(default)]
+	pub tool_search: bool,
+}
+
+// Shortened names end in `_` and an 8 hex digit hash, after at least one character of the name
+const MIN_NAME_LENGTH: usize = 10;
+
+fn deserialize_max_name_length<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+	D: serde::Deserializer<'de>,
+{
+	match Option::<usize>::deserialize(deserializer)? {
+		Some(max) if max < MIN_NAME_LENGTH => Err(serde::de::Error::custom(format!(
+			"max_name_length must be at least {}, got {}",
+			MIN_NAME_LENGTH, max
+		))),
+		max => Ok(max),
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub enum PrefixStrategy {
+	#[default]
+	#[serde(rename = "always")]
+	Always,
+	/// Only names offered by more than one target are prefixed.
+	#[serde(rename = "on_conflict")]
+	OnConflict,
+	/// Names are never prefixed. If several targets offer the same name, only the first target's is listed.
+	#[serde(rename = "never")]
+	Never,
 }
 
 #[derive(Debug)]
@@ -31,27 +113,37 @@ pub enum ServingError {
 }
 
 impl Listener {
+	pub fn options(&self) -> RelayOptions {
+		match self {
+			Listener::Sse { options, .. } | Listener::Stdio { options } => {
+				options.clone().unwrap_or_default()
+			},
+		}
+	}
+
 	pub async fn listen(
 		&self,
 		state: Arc<std::sync::RwLock<xds::XdsStore>>,
 		metrics: Arc<crate::relay::metrics::Metrics>,
 	) -> Result<(), ServingError> {
+		tokio::spawn(crate::relay::fetch_instructions(
+			state.clone(),
+			metrics.clone(),
+		));
 		match self {
-			Listener::Stdio {} => {
-				let relay = serve_server(
-					// TODO: This is a hack
-					crate::relay::Relay::new(state.clone(), rbac::Identity::empty(), metrics),
-					(tokio::io::stdin(), tokio::io::stdout()),
-				)
-				.await
-				.inspect_err(|e| {
-					tracing::error!("serving error: {:?}", e);
-				})
-				.unwrap();
-				tracing::info!("serving stdio");
-				relay
-					.waiting()
+			Listener::Stdio { .. } => {
+				// TODO: This is a hack
+				let relay = crate::relay::Relay::new(state.clone(), rbac::Identity::empty(), metrics);
+				let service = serve_server(relay.clone(), (tokio::io::stdin(), tokio::io::stdout()))
 					.await
+					.inspect_err(|e| {
+						tracing::error!("serving error: {:?}", e);
+					})
+					.unwrap();
+				tracing::info!("serving stdio");
+				let result = service.waiting().await;
+				relay.close().await;
+				result
 					.map_err(ServingError::StdIo)
 					.map(|_| ())
 					.inspect_err(|e| {
@@ -63,6 +155,7 @@ impl Listener {
 				port,
 				mode,
 				authn,
+				..
 			} => {
 				let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port))
 					.await
@@ -135,6 +228,6 @@ pub enum ListenerMode {
 
 impl Default for Listener {
 	fn default() -> Self {
-		Self::Stdio {}
+		Self::Stdio { options: None }
 	}
 }
@@ -1,12 +1,124 @@
 use openapiv3::Paths;
+use rmcp::model::LoggingLevel;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 pub mod backend;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Target {
 	pub name: String,
 	pub spec: TargetSpec,
+	pub timeouts: Timeouts,
+	pub retries: Option<RetryPolicy>,
+	pub circuit_breaker: Option<CircuitBreaker>,
+	pub tools: Vec<ToolOverride>,
+	pub filters: Filters,
+	pub min_log_level: Option<LoggingLevel>,
+	pub list_cache_ttl: Option<Duration>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Filters {
+	pub tools: Filter,
+	pub prompts: Filter,
+	pub resources: Filter,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Filter {
+	pub include: Vec<String>,
+	pub exclude: Vec<String>,
+}
+
+impl Filter {
+	pub fn allows(&self, name: &str) -> bool {
+		(self.include.is_empty() || self.include.iter().any(|p| glob_match(p, name)))
+			&& !self.exclude.iter().any(|p| glob_match(p, name))
+	}
+}
+
+/// Matches a glob pattern, where `*` matches any run of characters and `?` any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+	let (mut p, mut n) = (0, 0);
+	// The position after the last `*`, and the position in name it was matched up to
+	let mut backtrack = None;
+	while n < name.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				p += 1;
+				backtrack = Some((p, n));
+			},
+			Some(c) if *c == '?' || *c == name[n] => {
+				p += 1;
+				n += 1;
+			},
+			_ => match backtrack {
+				Some((bp, bn)) => {
+					p = bp;
+					n = bn + 1;
+					backtrack = Some((bp, bn + 1));
+				},
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ToolOverride {
+	pub name: String,
+	pub rename: Option<String>,
+	pub aliases: Vec<String>,
+	pub hide: bool,
+	pub description: Option<String>,
+	pub input_schema_patch: Option<serde_json::Map<String, serde_json::Value>>,
+	pub default_arguments: serde_json::Map<String, serde_json::Value>,
+	pub fixed_arguments: serde_json::Map<String, serde_json::Value>,
+	pub cache: Option<ResponseCache>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ResponseCache {
+	pub ttl: Duration,
+	pub max_entries: usize,
+	pub per_identity: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Timeouts {
+	pub connect: Option<Duration>,
+	pub list: Option<Duration>,
+	pub call: Option<Duration>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct RetryPolicy {
+	pub attempts: u32,
+	pub backoff: Duration,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CircuitBreaker {
+	pub consecutive_failures: u32,
+	pub open_interval: Duration,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Endpoint {
+	pub host: String,
+	pub port: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum LoadBalancing {
+	#[default]
+	RoundRobin,
+	/// The endpoint with the fewest open connections.
+	LeastConnections,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -18,12 +130,20 @@ pub enum TargetSpec {
 		port: u32,
 		path: String,
 		backend_auth: Option<backend::BackendAuthConfig>,
+		endpoints: Vec<Endpoint>,
+		dns_discovery: bool,
+		load_balancing: LoadBalancing,
 	},
 	#[serde(rename = "stdio")]
 	Stdio {
 		cmd: String,
 		args: Vec<String>,
 		env: HashMap<String, String>,
+		cwd: Option<String>,
+		clear_env: bool,
+		inherit_env: Vec<String>,
+		limits: Option<StdioLimits>,
+		stderr_level: Option<String>,
 	},
 	#[serde(rename = "openapi")]
 	OpenAPI {
@@ -31,9 +151,120 @@ pub enum TargetSpec {
 		port: u32,
 		schema: OpenAPISchema,
 	},
+	#[serde(rename = "composite")]
+	Composite { tools: Vec<CompositeTool> },
+	#[serde(rename = "static")]
+	Static {
+		prompts: Vec<StaticPrompt>,
+		resources: Vec<StaticResource>,
+	},
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StaticPrompt {
+	pub name: String,
+	pub description: Option<String>,
+	pub arguments: Vec<StaticPromptArgument>,
+	pub messages: Vec<StaticPromptMessage>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StaticPromptArgument {
+	pub name: String,
+	pub description: Option<String>,
+	pub required: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StaticPromptMessage {
+	pub role: PromptRole,
+	pub text: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum PromptRole {
+	#[default]
+	User,
+	Assistant,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StaticResource {
+	pub uri: String,
+	pub name: Option<String>,
+	pub description: Option<String>,
+	pub mime_type: Option<String>,
+	pub source: StaticSource,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum StaticSource {
+	Text(String),
+	File(String),
+	Directory(String),
 }
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CompositeTool {
+	pub name: String,
+	pub description: Option<String>,
+	pub input_schema: serde_json::Map<String, serde_json::Value>,
+	pub steps: Vec<CompositeStep>,
+	pub output: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CompositeStep {
+	pub id: String,
+	pub target: String,
+	pub tool: String,
+	pub arguments: serde_json::Map<String, serde_json::Value>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct StdioLimits {
+	pub memory_bytes: Option<u64>,
+	pub cpu_seconds: Option<u64>,
+	pub open_files: Option<u64>,
+	pub kill_process_group: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct OpenAPISchema {
 	// The crate OpenAPI type requires a lot more, we only need paths for now so use only a subset of it.
 	pub paths: Paths,
 }
+
+#[test]
+fn test_filter_allows() {
+	let filter = Filter {
+		include: vec!["get_*".to_string(), "echo".to_string()],
+		exclude: vec!["get_*_secret".to_string()],
+	};
+	assert!(filter.allows("echo"));
+	assert!(filter.allows("get_weather"));
+	assert!(!filter.allows("get_api_secret"));
+	assert!(!filter.allows("delete_file"));
+	assert!(Filter::default().allows("anything"));
+}
+
+#[test]
+fn test_glob_match() {
+	assert!(glob_match("*", ""));
+	assert!(glob_match("*", "anything"));
+	assert!(glob_match("get_*", "get_"));
+	assert!(glob_match("get_*", "get_weather"));
+	assert!(!glob_match("get_*", "set_weather"));
+	assert!(glob_match("*_secret", "get_api_secret"));
+	assert!(glob_match("a*b*c", "aXbYbZc"));
+	assert!(!glob_match("a*b*c", "aXbYbZ"));
+	assert!(glob_match("echo?", "echo1"));
+	assert!(!glob_match("echo?", "echo"));
+	assert!(!glob_match("echo?", "echo12"));
+	assert!(glob_match("", ""));
+	assert!(!glob_match("", "echo"));
+	assert!(!glob_match("?", ""));
+	// Wildcards match characters, not bytes
+	assert!(glob_match("caf?", "café"));
+	assert!(glob_match("*é_*", "résumé_tool"));
+	assert!(!glob_match("??", "é"));
+}
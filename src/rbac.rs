@@ -79,6 +79,7 @@ pub enum ResourceType {
 	Tool { id: String },
 	Prompt { id: String },
 	Resource { id: String },
+	Sampling { id: String },
 }
 
 impl TryFrom<&rule::Resource> for ResourceType {
@@ -94,6 +95,9 @@ impl TryFrom<&rule::Resource> for ResourceType {
 			Ok(rule::resource::ResourceType::Resource) => Ok(ResourceType::Resource {
 				id: value.id.clone(),
 			}),
+			Ok(rule::resource::ResourceType::Sampling) => Ok(ResourceType::Sampling {
+				id: value.id.clone(),
+			}),
 			_ => Err(anyhow::anyhow!("Invalid resource type")),
 		}
 	}
@@ -106,6 +110,7 @@ impl ResourceType {
 			(ResourceType::Tool { id: a }, ResourceType::Tool { id: b }) => a == b || a == "*",
 			(ResourceType::Prompt { id: a }, ResourceType::Prompt { id: b }) => a == b || a == "*",
 			(ResourceType::Resource { id: a }, ResourceType::Resource { id: b }) => a == b || a == "*",
+			(ResourceType::Sampling { id: a }, ResourceType::Sampling { id: b }) => a == b || a == "*",
 			_ => false,
 		}
 	}
@@ -162,6 +167,10 @@ impl Identity {
 			Matcher::Equals => self.get_claim(key) == Some(value),
 		}
 	}
+	pub fn claim(&self, key: &str) -> Option<&Value> {
+		self.claims.as_ref().and_then(|claims| claims.get(key))
+	}
+
 	fn get_claim(&self, key: &str) -> Option<&str> {
 		match &self.claims {
 			Some(claims) => claims.get(key).and_then(|v| v.as_str()),
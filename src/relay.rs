@@ -1,28 +1,51 @@
 use crate::backend::BackendAuth;
 use crate::metrics::Recorder;
-use crate::outbound::{OpenAPISchema, Target, TargetSpec};
+use crate::outbound::{
+	CircuitBreaker, CompositeTool, Endpoint, Filters, LoadBalancing, OpenAPISchema, RetryPolicy,
+	Target, TargetSpec, Timeouts, ToolOverride,
+};
 use crate::rbac;
 use crate::xds::XdsStore;
+use balancer::{Address, CountGuard, EndpointHandle};
+use client::{Downstream, UpstreamClient};
+use health::{BreakerState, UpstreamStore};
 use http::{HeaderMap, HeaderValue, Method, header::AUTHORIZATION};
 use itertools::Itertools;
+use namespace::{NameTable, Naming, ResourceNamespace};
+use pagination::Cursor;
 use rmcp::RoleClient;
 use rmcp::serve_client;
-use rmcp::service::RunningService;
-use rmcp::transport::child_process::TokioChildProcess;
+use rmcp::service::{Peer, PeerRequestOptions, RunningService};
 use rmcp::transport::sse::{ReqwestSseClient, SseTransport};
 use rmcp::{
 	Error as McpError, RoleServer, ServerHandler, model::CallToolRequestParam, model::Tool, model::*,
 	service::RequestContext,
 };
-use serde_json::json;
+use serde_json::{Value, json};
+use static_target::StaticHandler;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Command;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 use tracing::instrument;
 
+pub mod balancer;
+pub mod cache;
+mod client;
+mod composite;
+pub mod health;
 pub mod metrics;
+mod namespace;
+mod pagination;
+mod search;
+mod static_target;
+mod stdio;
+mod transform;
+mod validate;
 
 #[derive(Clone)]
 pub struct Relay {
@@ -30,6 +53,11 @@ pub struct Relay {
 	pool: Arc<RwLock<ConnectionPool>>,
 	id: rbac::Identity,
 	metrics: Arc<metrics::Metrics>,
+	tool_names: NameTable,
+	prompt_names: NameTable,
+	// The input schema of each tool, by the name it was listed under
+	tool_schemas: Arc<std::sync::Mutex<HashMap<String, Arc<validate::ToolSchema>>>>,
+	downstream: Downstream,
 }
 
 impl Relay {
@@ -38,64 +66,627 @@ impl Relay {
 		id: rbac::Identity,
 		metrics: Arc<metrics::Metrics>,
 	) -> Self {
+		let downstream = Downstream::new(state.clone(), id.clone());
 		Self {
 			state: state.clone(),
-			pool: Arc::new(RwLock::new(ConnectionPool::new(state.clone()))),
+			pool: Arc::new(RwLock::new(ConnectionPool::new(
+				state.clone(),
+				metrics.clone(),
+				downstream.clone(),
+			))),
 			id,
 			metrics,
+			tool_names: NameTable::default(),
+			prompt_names: NameTable::default(),
+			tool_schemas: Arc::new(std::sync::Mutex::new(HashMap::new())),
+			downstream,
+		}
+	}
+
+	fn naming(&self) -> Naming {
+		Naming::new(&self.state.read().unwrap().listener.options())
+	}
+
+	fn tool_overrides(&self, target: &str) -> Vec<ToolOverride> {
+		match self.state.read().unwrap().targets.get(target) {
+			Some(target) => target.tools.clone(),
+			None => vec![],
+		}
+	}
+
+	fn filters(&self, target: &str) -> Filters {
+		match self.state.read().unwrap().targets.get(target) {
+			Some(target) => target.filters.clone(),
+			None => Filters::default(),
+		}
+	}
+
+	/// Lists a page of tools from every target, named by the naming options of the listener.
+	async fn list_named_tools(
+		&self,
+		request: PaginatedRequestParam,
+		context: &RequestContext<RoleServer>,
+	) -> Result<(Vec<Tool>, Option<String>), McpError> {
+		let (tools, next_cursor) = self
+			.list_targets("tool", request, context, |name, svc, request| async move {
+				let r = svc.as_ref().read().await.list_tools(request).await?;
+				let filter = self.filters(&name).tools;
+				let tools = r
+					.tools
+					.into_iter()
+					.filter(|t| filter.allows(&t.name))
+					.collect();
+				let tools = transform::apply_tool_overrides(&self.tool_overrides(&name), tools)
+					.into_iter()
+					.map(|t| (name.clone(), t))
+					.collect::<Vec<_>>();
+				Ok((tools, r.next_cursor))
+			})
+			.await?;
+		let names = self.naming().assign(
+			&self.tool_names,
+			&tools
+				.iter()
+				.map(|(target, t)| (target.as_str(), t.name.as_ref()))
+				.collect::<Vec<_>>(),
+		);
+		let tools: Vec<Tool> = tools
+			.into_iter()
+			.zip(names)
+			.filter_map(|((_, t), name)| {
+				Some(Tool {
+					name: Cow::Owned(name?),
+					..t
+				})
+			})
+			.collect();
+		let mut schemas = self.tool_schemas.lock().unwrap();
+		for tool in &tools {
+			let name = tool.name.as_ref();
+			if schemas
+				.get(name)
+				.is_none_or(|schema| !schema.is(&tool.input_schema))
+			{
+				let schema = validate::ToolSchema::new(tool.input_schema.clone());
+				schemas.insert(name.to_string(), Arc::new(schema));
+			}
+		}
+		drop(schemas);
+		Ok((tools, next_cursor))
+	}
+
+	/// Lists a page of prompts from every target, named by the naming options of the listener.
+	async fn list_named_prompts(
+		&self,
+		request: PaginatedRequestParam,
+		context: &RequestContext<RoleServer>,
+	) -> Result<(Vec<Prompt>, Option<String>), McpError> {
+		let (prompts, next_cursor) = self
+			.list_targets(
+				"prompt",
+				request,
+				context,
+				|name, svc, request| async move {
+					let r = svc.as_ref().read().await.list_prompts(request).await?;
+					let filter = self.filters(&name).prompts;
+					let prompts = r
+						.prompts
+						.into_iter()
+						.filter(|p| filter.allows(&p.name))
+						.map(|p| (name.clone(), p))
+						.collect::<Vec<_>>();
+					Ok((prompts, r.next_cursor))
+				},
+			)
+			.await?;
+		let names = self.naming().assign(
+			&self.prompt_names,
+			&prompts
+				.iter()
+				.map(|(target, p)| (target.as_str(), p.name.as_str()))
+				.collect::<Vec<_>>(),
+		);
+		let prompts = prompts
+			.into_iter()
+			.zip(names)
+			.filter_map(|((_, p), name)| Some(Prompt { name: name?, ..p }))
+			.collect();
+		Ok((prompts, next_cursor))
+	}
+
+	/// Maps a tool name handed out to the client back to its target and upstream name.
+	/// Names missing from the table, e.g. because the client called the tool without listing first,
+	/// are resolved by listing every target.
+	async fn resolve_tool(
+		&self,
+		exposed: &str,
+		context: &RequestContext<RoleServer>,
+	) -> Result<(String, String), McpError> {
+		if let Some(found) = self.resolve_known(&self.tool_names, exposed) {
+			return Ok(found);
+		}
+		self.refresh_tools(context).await?;
+		self
+			.tool_names
+			.get(exposed)
+			.ok_or(McpError::invalid_params("unknown tool", None))
+	}
+
+	/// Lists every page of tools, so the names and schemas handed out are known.
+	async fn refresh_tools(
+		&self,
+		context: &RequestContext<RoleServer>,
+	) -> Result<Vec<Tool>, McpError> {
+		let mut request = None;
+		let mut tools = Vec::new();
+		for _ in 0..pagination::MAX_PAGES {
+			let (page, next) = self.list_named_tools(request, context).await?;
+			tools.extend(page);
+			match next {
+				Some(cursor) => request = pagination::request(Some(cursor)),
+				None => break,
+			}
+		}
+		Ok(tools)
+	}
+
+	/// Answers a search_tools call with the best matching tools the caller may call.
+	async fn search_tools(
+		&self,
+		arguments: Option<&JsonObject>,
+		context: &RequestContext<RoleServer>,
+	) -> Result<CallToolResult, McpError> {
+		let (query, limit) = search::search_request(arguments)?;
+		let tools = self
+			.refresh_tools(context)
+			.await?
+			.into_iter()
+			.filter(|tool| self.tool_allowed(&tool.name))
+			.collect();
+		let tools = search::rank(tools, &query, limit);
+		let json =
+			serde_json::to_string(&tools).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+		Ok(CallToolResult {
+			content: vec![Content::text(json)],
+			is_error: None,
+		})
+	}
+
+	/// Returns whether RBAC lets the caller call a tool, by the name it is listed under.
+	fn tool_allowed(&self, exposed: &str) -> bool {
+		let Some((target, listed)) = self.tool_names.get(exposed) else {
+			return false;
+		};
+		let overrides = self.tool_overrides(&target);
+		let Some(tool) = transform::upstream_tool_name(&overrides, &listed) else {
+			return false;
+		};
+		self.state.read().unwrap().policies.validate(
+			&rbac::ResourceType::Tool {
+				id: format!("{}:{}", target, tool),
+			},
+			&self.id,
+		)
+	}
+
+	/// Validates the arguments of a tool call if the listener asks for it.
+	async fn validate_arguments(
+		&self,
+		exposed: &str,
+		arguments: Option<&JsonObject>,
+		context: &RequestContext<RoleServer>,
+	) -> Result<(), McpError> {
+		if !self
+			.state
+			.read()
+			.unwrap()
+			.listener
+			.options()
+			.validate_arguments
+		{
+			return Ok(());
+		}
+		let cached = { self.tool_schemas.lock().unwrap().get(exposed).cloned() };
+		let schema = match cached {
+			Some(schema) => schema,
+			None => {
+				self.refresh_tools(context).await?;
+				let schema = { self.tool_schemas.lock().unwrap().get(exposed).cloned() };
+				schema.ok_or(McpError::invalid_params("unknown tool", None))?
+			},
+		};
+		schema.validate(arguments)
+	}
+
+	/// Maps a prompt name handed out to the client back to its target and upstream name.
+	async fn resolve_prompt(
+		&self,
+		exposed: &str,
+		context: &RequestContext<RoleServer>,
+	) -> Result<(String, String), McpError> {
+		if let Some(found) = self.resolve_known(&self.prompt_names, exposed) {
+			return Ok(found);
+		}
+		let mut request = None;
+		for _ in 0..pagination::MAX_PAGES {
+			match self.list_named_prompts(request, context).await?.1 {
+				Some(cursor) => request = pagination::request(Some(cursor)),
+				None => break,
+			}
+		}
+		self
+			.prompt_names
+			.get(exposed)
+			.ok_or(McpError::invalid_params("unknown prompt", None))
+	}
+
+	fn resolve_known(&self, table: &NameTable, exposed: &str) -> Option<(String, String)> {
+		table.get(exposed).or_else(|| {
+			let naming = self.naming();
+			let state = self.state.read().unwrap();
+			naming
+				.parse(exposed, |target| state.targets.get(target).is_some())
+				.map(|(target, name)| (target.to_string(), name.to_string()))
+		})
+	}
+
+	/// Lists one page of items from every target, following the per-target cursors in the request.
+	/// If the listener drains upstream pages, every page of every target is fetched and the merged
+	/// list is paged by the proxy instead.
+	async fn list_targets<T, F, Fut>(
+		&self,
+		resource_type: &str,
+		request: PaginatedRequestParam,
+		context: &RequestContext<RoleServer>,
+		list: F,
+	) -> Result<(Vec<T>, Option<String>), McpError>
+	where
+		F: Fn(String, Arc<RwLock<UpstreamTarget>>, PaginatedRequestParam) -> Fut,
+		Fut: Future<Output = Result<(Vec<T>, Option<String>), UpstreamError>>,
+		T: Clone + Send + Sync + 'static,
+	{
+		let cursor = Cursor::from_request(&request).map_err(|e| McpError::invalid_params(e, None))?;
+		let options = self.state.read().unwrap().listener.options();
+		// Names prefixed on conflict can only be assigned once every tool or prompt is known
+		let drain = options.drain_pages
+			|| (matches!(resource_type, "tool" | "prompt") && Naming::new(&options).needs_every_page());
+		let (cursors, offset) = match (cursor, drain) {
+			(None, _) => (None, 0),
+			(Some(Cursor::Targets(cursors)), false) => (Some(cursors), 0),
+			(Some(Cursor::Offset(offset)), true) => (None, offset),
+			_ => {
+				return Err(McpError::invalid_params(
+					"cursor was not issued by this listener",
+					None,
+				));
+			},
+		};
+
+		let pool = self.pool.read().await;
+		// Targets already listed to the end are left out of the cursor, and are not connected to again
+		let all = pool
+			.iter(|name| cursors.as_ref().is_none_or(|c| c.contains_key(name)))
+			.await
+			.map(|(name, svc)| {
+				let policy = pool.policy(&name);
+				let (generation, ttl) = {
+					let state = self.state.read().unwrap();
+					let ttl = state.targets.get(&name).and_then(|t| t.list_cache_ttl);
+					(state.targets.generation(&name), ttl)
+				};
+				let lists = self.downstream.lists();
+				let mut cursor = cursors.as_ref().and_then(|c| c.get(&name).cloned());
+				let list = &list;
+				async move {
+					let svc = match svc {
+						Ok(svc) => svc,
+						Err(e) => return Err((name, e)),
+					};
+					let mut items = Vec::new();
+					// Whether any page came from the target rather than the cache, so its health is known
+					let mut contacted = false;
+					for _ in 0..pagination::MAX_PAGES {
+						let cached =
+							ttl.and_then(|_| lists.get::<T>(&name, resource_type, cursor.as_deref(), generation));
+						let fetched = cached.is_none();
+						contacted |= fetched;
+						let result = match cached {
+							Some(page) => Ok(page),
+							None => {
+								let request = pagination::request(cursor.clone());
+								policy
+									.list(|| list(name.clone(), svc.clone(), request.clone()))
+									.await
+							},
+						};
+						match result {
+							Ok((page, next)) => {
+								if let Some(ttl) = ttl.filter(|_| fetched) {
+									lists.insert(
+										&name,
+										resource_type,
+										cursor.as_deref(),
+										generation,
+										ttl,
+										(page.clone(), next.clone()),
+									);
+								}
+								items.extend(page);
+								cursor = next;
+							},
+							Err(e) => return Err((name, e)),
+						}
+						if !drain || cursor.is_none() {
+							break;
+						}
+					}
+					Ok((name, contacted, (items, cursor)))
+				}
+			});
+
+		let (mut results, errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
+			.await
+			.into_iter()
+			.partition_result();
+		pool.report_all(&results, &errors).await;
+		self
+			.report_list_errors(resource_type, &errors, context)
+			.await;
+
+		// Merge in a stable order, so offsets into the merged list stay valid between requests
+		results.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+		let mut items = Vec::new();
+		let mut next = BTreeMap::new();
+		for (name, _, (page, cursor)) in results {
+			items.extend(page);
+			if let Some(cursor) = cursor {
+				next.insert(name, cursor);
+			}
+		}
+		if drain {
+			if !next.is_empty() {
+				tracing::warn!(
+					"stopped listing {}s from targets {:?} after {} pages",
+					resource_type,
+					next.keys().collect::<Vec<_>>(),
+					pagination::MAX_PAGES
+				);
+			}
+			return Ok(pagination::page(items, offset, options.page_size));
+		}
+		let next_cursor = match next.is_empty() {
+			true => None,
+			false => Some(Cursor::Targets(next).encode()),
+		};
+		Ok((items, next_cursor))
+	}
+
+	/// Maps a resource URI issued by the proxy to its target and upstream URI,
+	/// checking that the caller may access it.
+	/// Rules match resources as `<target>:<upstream uri>`, whatever the URI the client uses.
+	fn resolve_resource(&self, uri: &str) -> Result<(String, String), McpError> {
+		let namespace = self.resource_namespace();
+		let (service_name, resource) = namespace
+			.unwrap(uri)
+			.ok_or(McpError::resource_not_found("unknown resource uri", None))?;
+		if !self.state.read().unwrap().policies.validate(
+			&rbac::ResourceType::Resource {
+				id: format!("{}:{}", service_name, resource),
+			},
+			&self.id,
+		) {
+			return Err(McpError::invalid_request("not allowed", None));
+		}
+		if !self.filters(service_name).resources.allows(resource) {
+			return Err(McpError::resource_not_found("unknown resource uri", None));
+		}
+		Ok((service_name.to_string(), resource.to_string()))
+	}
+
+	/// Subscribes to, or unsubscribes from, a resource on its target.
+	async fn forward_subscription(
+		&self,
+		service_name: &str,
+		resource: &str,
+		subscribe: bool,
+	) -> Result<(), McpError> {
+		let pool = self.pool.read().await;
+		let service = pool.acquire(service_name).await?;
+		let policy = pool.policy(service_name);
+		let upstream = service.as_ref().read().await;
+		let result = match subscribe {
+			true => policy.read(|| upstream.subscribe(resource)).await,
+			false => policy.read(|| upstream.unsubscribe(resource)).await,
+		};
+		match result {
+			Ok(()) => {
+				pool.succeeded(service_name);
+				Ok(())
+			},
+			Err(e) => {
+				pool.report(service_name, &e).await;
+				Err(e.into())
+			},
+		}
+	}
+
+	/// Unsubscribes from the resources the client is still subscribed to, once its session ended.
+	/// Targets do not see the session end while their connection is kept open.
+	pub async fn close(&self) {
+		let connections: HashMap<_, _> = self
+			.pool
+			.read()
+			.await
+			.connected()
+			.await
+			.into_iter()
+			.collect();
+		for (service_name, resource) in self.downstream.clear_subscriptions() {
+			let Some(connection) = connections.get(&service_name) else {
+				continue;
+			};
+			if let Err(e) = connection.read().await.unsubscribe(&resource).await {
+				tracing::debug!(
+					"failed to unsubscribe from {} on target {}: {}",
+					resource,
+					service_name,
+					e
+				);
+			}
+		}
+	}
+
+	fn resource_namespace(&self) -> ResourceNamespace {
+		let options = self.state.read().unwrap().listener.options();
+		ResourceNamespace::new(options.resource_scheme.as_deref())
+	}
+
+	/// Logs and counts each target that was left out of a list response,
+	/// and warns the client about them if the listener asks for it.
+	async fn report_list_errors(
+		&self,
+		resource_type: &str,
+		errors: &[(String, UpstreamError)],
+		context: &RequestContext<RoleServer>,
+	) {
+		if errors.is_empty() {
+			return;
+		}
+		for (name, error) in errors {
+			tracing::warn!(
+				"error listing {}s from target {}: {}",
+				resource_type,
+				name,
+				error
+			);
+			self.metrics.record(
+				&metrics::ListError {
+					server: name.clone(),
+					resource_type: resource_type.to_string(),
+					error_type: error.error_code(),
+				},
+				(),
+			);
+		}
+		if !self
+			.state
+			.read()
+			.unwrap()
+			.listener
+			.options()
+			.warn_unavailable_targets
+		{
+			return;
+		}
+		let targets: Vec<&str> = errors.iter().map(|(name, _)| name.as_str()).collect();
+		let notification = LoggingMessageNotificationParam {
+			level: LoggingLevel::Warning,
+			logger: Some("mcp-proxy".to_string()),
+			data: json!({
+				"message": format!("{}s from some targets are missing because they are unavailable", resource_type),
+				"unavailableTargets": targets,
+			}),
+		};
+		if let Err(e) = context.peer.notify_logging_message(notification).await {
+			tracing::debug!("error sending unavailable targets warning: {}", e);
 		}
 	}
 }
 
 // TODO: lists and gets can be macros
 impl ServerHandler for Relay {
+	fn get_peer(&self) -> Option<Peer<RoleServer>> {
+		self.downstream.get()
+	}
+
+	fn set_peer(&mut self, peer: Peer<RoleServer>) {
+		self.downstream.set(peer);
+	}
+
+	/// Tells the connected targets that the roots of the client changed, so they list them again.
+	async fn on_roots_list_changed(&self) {
+		let connections = self.pool.read().await.connected().await;
+		for (name, connection) in connections {
+			if let Err(e) = connection.read().await.notify_roots_list_changed().await {
+				tracing::debug!("failed to notify target {} of changed roots: {}", name, e);
+			}
+		}
+	}
+
 	#[instrument(level = "debug", skip_all)]
 	fn get_info(&self) -> ServerInfo {
+		let state = self.state.read().unwrap();
+		let options = state.listener.options();
+		let mut server_info = Implementation::from_build_env();
+		if let Some(name) = options.server_name.clone() {
+			server_info.name = name;
+		}
+		if let Some(version) = options.server_version.clone() {
+			server_info.version = version;
+		}
+		let mut instructions = options.instructions.clone();
+		if options.compose_instructions {
+			let targets = state
+				.upstreams
+				.instructions()
+				.iter()
+				.filter(|(name, _)| state.targets.get(name).is_some())
+				.map(|(name, i)| format!("## {}\n\n{}", name, i.trim()));
+			let composed = instructions.iter().cloned().chain(targets).join("\n\n");
+			instructions = Some(composed).filter(|i| !i.is_empty());
+		}
+		// The protocol version is not negotiated: rmcp answers the initialize request with this info,
+		// without calling the handler or looking at the version the client asked for, and it only
+		// implements 2024-11-05
 		ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities {
-                experimental: None,
-                logging: None,
-                prompts: Some(PromptsCapability::default()),
-                resources: Some(ResourcesCapability::default()),
-                tools: Some(ToolsCapability {
-                    list_changed: None,
-                }),
-            },
-            server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "This server provides a counter tool that can increment and decrement values. The counter starts at 0 and can be modified using the 'increment' and 'decrement' tools. Use 'get_value' to check the current count.".to_string(),
-            ),
-        }
+			protocol_version: ProtocolVersion::V_2024_11_05,
+			capabilities: ServerCapabilities {
+				experimental: None,
+				logging: Some(JsonObject::new()),
+				prompts: Some(PromptsCapability::default()),
+				resources: Some(ResourcesCapability {
+					subscribe: Some(true),
+					list_changed: None,
+				}),
+				tools: Some(ToolsCapability { list_changed: None }),
+			},
+			server_info,
+			instructions,
+		}
 	}
 
 	#[instrument(level = "debug", skip_all)]
 	async fn list_resources(
 		&self,
 		request: PaginatedRequestParam,
-		_context: RequestContext<RoleServer>,
+		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListResourcesResult, McpError> {
-		let pool = self.pool.read().await;
-		let all = pool.iter().await.map(|(_name, svc)| {
-			let svc = svc.clone();
-			let request = request.clone();
-			async move {
-				match svc.as_ref().read().await.list_resources(request).await {
-					Ok(r) => Ok(r.resources),
-					Err(e) => Err(e),
-				}
-			}
-		});
-
-		// TODO: Handle errors
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
-			.await
-			.into_iter()
-			.partition_result();
+		let namespace = &self.resource_namespace();
+		let (resources, next_cursor) = self
+			.list_targets(
+				"resource",
+				request,
+				&context,
+				|name, svc, request| async move {
+					let r = svc.as_ref().read().await.list_resources(request).await?;
+					let filter = self.filters(&name).resources;
+					let resources = r
+						.resources
+						.into_iter()
+						.filter(|resource| filter.allows(&resource.raw.uri))
+						.map(|mut resource| {
+							resource.raw.uri = namespace.wrap(&name, &resource.raw.uri);
+							resource
+						})
+						.collect::<Vec<_>>();
+					Ok((resources, r.next_cursor))
+				},
+			)
+			.await?;
 
 		Ok(ListResourcesResult {
-			resources: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			resources,
+			next_cursor,
 		})
 	}
 
@@ -103,30 +694,35 @@ impl ServerHandler for Relay {
 	async fn list_resource_templates(
 		&self,
 		request: PaginatedRequestParam,
-		_context: RequestContext<RoleServer>,
+		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListResourceTemplatesResult, McpError> {
-		let pool = self.pool.read().await;
-		let all = pool.iter().await.map(|(_name, svc)| {
-			let svc = svc.clone();
-			let request = request.clone();
-			async move {
-				match svc
-					.as_ref()
-					.read()
-					.await
-					.list_resource_templates(request)
-					.await
-				{
-					Ok(r) => Ok(r.resource_templates),
-					Err(e) => Err(e),
-				}
-			}
-		});
-
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
-			.await
-			.into_iter()
-			.partition_result();
+		let namespace = &self.resource_namespace();
+		let (resource_templates, next_cursor) = self
+			.list_targets(
+				"resource_template",
+				request,
+				&context,
+				|name, svc, request| async move {
+					let r = svc
+						.as_ref()
+						.read()
+						.await
+						.list_resource_templates(request)
+						.await?;
+					let filter = self.filters(&name).resources;
+					let templates = r
+						.resource_templates
+						.into_iter()
+						.filter(|template| filter.allows(&template.raw.uri_template))
+						.map(|mut template| {
+							template.raw.uri_template = namespace.wrap(&name, &template.raw.uri_template);
+							template
+						})
+						.collect::<Vec<_>>();
+					Ok((templates, r.next_cursor))
+				},
+			)
+			.await?;
 
 		self.metrics.clone().record(
 			&metrics::ListCall {
@@ -136,8 +732,8 @@ impl ServerHandler for Relay {
 		);
 
 		Ok(ListResourceTemplatesResult {
-			resource_templates: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			resource_templates,
+			next_cursor,
 		})
 	}
 
@@ -145,33 +741,9 @@ impl ServerHandler for Relay {
 	async fn list_prompts(
 		&self,
 		request: PaginatedRequestParam,
-		_context: RequestContext<RoleServer>,
+		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListPromptsResult, McpError> {
-		let pool = self.pool.read().await;
-		let all = pool.iter().await.map(|(_name, svc)| {
-			let svc = svc.clone();
-			let request = request.clone();
-			async move {
-				match svc.as_ref().read().await.list_prompts(request).await {
-					Ok(r) => Ok(
-						r.prompts
-							.into_iter()
-							.map(|p| Prompt {
-								name: format!("{}:{}", _name, p.name),
-								description: p.description,
-								arguments: p.arguments,
-							})
-							.collect::<Vec<_>>(),
-					),
-					Err(e) => Err(e),
-				}
-			}
-		});
-
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
-			.await
-			.into_iter()
-			.partition_result();
+		let (prompts, next_cursor) = self.list_named_prompts(request, &context).await?;
 
 		self.metrics.clone().record(
 			&metrics::ListCall {
@@ -180,8 +752,8 @@ impl ServerHandler for Relay {
 			(),
 		);
 		Ok(ListPromptsResult {
-			prompts: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			prompts,
+			next_cursor,
 		})
 	}
 
@@ -197,18 +769,10 @@ impl ServerHandler for Relay {
 		request: ReadResourceRequestParam,
 		_context: RequestContext<RoleServer>,
 	) -> std::result::Result<ReadResourceResult, McpError> {
-		if !self.state.read().unwrap().policies.validate(
-			&rbac::ResourceType::Resource {
-				id: request.uri.to_string(),
-			},
-			&self.id,
-		) {
-			return Err(McpError::invalid_request("not allowed", None));
-		}
-		let uri = request.uri.to_string();
-		let (service_name, resource) = uri.split_once(':').unwrap();
+		let (service_name, resource) = &self.resolve_resource(&request.uri)?;
+		let namespace = self.resource_namespace();
 		let pool = self.pool.read().await;
-		let service = pool.get(service_name).await.unwrap();
+		let service = pool.acquire(service_name).await?;
 		let req = ReadResourceRequestParam {
 			uri: resource.to_string(),
 		};
@@ -220,10 +784,71 @@ impl ServerHandler for Relay {
 			},
 			(),
 		);
-		match service.as_ref().read().await.read_resource(req).await {
-			Ok(r) => Ok(r),
-			Err(e) => Err(e.into()),
+		let policy = pool.policy(service_name);
+		let upstream = service.as_ref().read().await;
+		match policy.read(|| upstream.read_resource(req.clone())).await {
+			Ok(mut r) => {
+				pool.succeeded(service_name);
+				for contents in r.contents.iter_mut() {
+					namespace.wrap_contents(service_name, contents);
+				}
+				Ok(r)
+			},
+			Err(e) => {
+				pool.report(service_name, &e).await;
+				Err(e.into())
+			},
+		}
+	}
+
+	#[instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        name=%request.uri,
+    ),
+  )]
+	async fn subscribe(
+		&self,
+		request: SubscribeRequestParam,
+		_context: RequestContext<RoleServer>,
+	) -> std::result::Result<(), McpError> {
+		let (service_name, resource) = &self.resolve_resource(&request.uri)?;
+		// Each target is subscribed to once, however often the client subscribes
+		if !self
+			.downstream
+			.subscribe(service_name, resource, &request.uri)
+		{
+			return Ok(());
 		}
+		let result = self
+			.forward_subscription(service_name, resource, true)
+			.await;
+		if result.is_err() {
+			self.downstream.unsubscribe(service_name, resource);
+		}
+		result
+	}
+
+	#[instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        name=%request.uri,
+    ),
+  )]
+	async fn unsubscribe(
+		&self,
+		request: UnsubscribeRequestParam,
+		_context: RequestContext<RoleServer>,
+	) -> std::result::Result<(), McpError> {
+		let (service_name, resource) = &self.resolve_resource(&request.uri)?;
+		if !self.downstream.unsubscribe(service_name, resource) {
+			return Ok(());
+		}
+		self
+			.forward_subscription(service_name, resource, false)
+			.await
 	}
 
 	#[instrument(
@@ -236,20 +861,22 @@ impl ServerHandler for Relay {
 	async fn get_prompt(
 		&self,
 		request: GetPromptRequestParam,
-		_context: RequestContext<RoleServer>,
+		context: RequestContext<RoleServer>,
 	) -> std::result::Result<GetPromptResult, McpError> {
+		let (service_name, prompt) = &self.resolve_prompt(&request.name, &context).await?;
+		if !self.filters(service_name).prompts.allows(prompt) {
+			return Err(McpError::invalid_params("unknown prompt", None));
+		}
 		if !self.state.read().unwrap().policies.validate(
 			&rbac::ResourceType::Prompt {
-				id: request.name.to_string(),
+				id: format!("{}:{}", service_name, prompt),
 			},
 			&self.id,
 		) {
 			return Err(McpError::invalid_request("not allowed", None));
 		}
-		let prompt_name = request.name.to_string();
-		let (service_name, prompt) = prompt_name.split_once(':').unwrap();
 		let pool = self.pool.read().await;
-		let service = pool.get(service_name).await.unwrap();
+		let service = pool.acquire(service_name).await?;
 		let req = GetPromptRequestParam {
 			name: prompt.to_string(),
 			arguments: request.arguments,
@@ -262,46 +889,102 @@ impl ServerHandler for Relay {
 			},
 			(),
 		);
-		match service.as_ref().read().await.get_prompt(req).await {
-			Ok(r) => Ok(r),
-			Err(e) => Err(e.into()),
+		let policy = pool.policy(service_name);
+		let upstream = service.as_ref().read().await;
+		match policy.read(|| upstream.get_prompt(req.clone())).await {
+			Ok(r) => {
+				pool.succeeded(service_name);
+				Ok(r)
+			},
+			Err(e) => {
+				pool.report(service_name, &e).await;
+				Err(e.into())
+			},
 		}
 	}
 
+	/// Sets the log level on every target the session uses, including those it connects to later.
 	#[instrument(level = "debug", skip_all)]
-	async fn list_tools(
+	async fn set_level(
 		&self,
-		request: PaginatedRequestParam,
+		request: SetLevelRequestParam,
 		_context: RequestContext<RoleServer>,
-	) -> std::result::Result<ListToolsResult, McpError> {
-		// TODO: Use iterators
-		// TODO: Handle individual errors
-		// TODO: Do we want to handle pagination here, or just pass it through?
-		let pool = self.pool.read().await;
-		let all = pool.iter().await.map(|(name, svc)| {
-			let svc = svc.clone();
-			let request = request.clone();
-			async move {
-				match svc.as_ref().read().await.list_tools(request).await {
-					Ok(r) => Ok(
-						r.tools
-							.into_iter()
-							.map(|t| Tool {
-								name: Cow::Owned(format!("{}:{}", name, t.name)),
-								description: t.description,
-								input_schema: t.input_schema,
-							})
-							.collect::<Vec<_>>(),
-					),
-					Err(e) => Err(e),
-				}
+	) -> std::result::Result<(), McpError> {
+		self.downstream.set_level(request.level.clone());
+		let connections = self.pool.read().await.connected().await;
+		for (name, connection) in connections {
+			if let Err(e) = connection
+				.read()
+				.await
+				.set_level(request.level.clone())
+				.await
+			{
+				tracing::debug!("failed to set the log level of target {}: {}", name, e);
 			}
-		});
+		}
+		Ok(())
+	}
 
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
-			.await
-			.into_iter()
-			.partition_result();
+	#[instrument(level = "debug", skip_all)]
+	async fn complete(
+		&self,
+		request: CompleteRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<CompleteResult, McpError> {
+		// Completions are routed like the prompt or resource template they complete an argument of
+		let (service_name, r#ref) = match &request.r#ref {
+			Reference::Prompt(prompt) => {
+				let (service_name, name) = self.resolve_prompt(&prompt.name, &context).await?;
+				if !self.filters(&service_name).prompts.allows(&name) {
+					return Err(McpError::invalid_params("unknown prompt", None));
+				}
+				if !self.state.read().unwrap().policies.validate(
+					&rbac::ResourceType::Prompt {
+						id: format!("{}:{}", service_name, name),
+					},
+					&self.id,
+				) {
+					return Err(McpError::invalid_request("not allowed", None));
+				}
+				(service_name, Reference::Prompt(PromptReference { name }))
+			},
+			Reference::Resource(resource) => {
+				let (service_name, uri) = self.resolve_resource(&resource.uri)?;
+				(service_name, Reference::Resource(ResourceReference { uri }))
+			},
+		};
+		let service_name = &service_name;
+		let pool = self.pool.read().await;
+		let service = pool.acquire(service_name).await?;
+		let req = CompleteRequestParam {
+			r#ref,
+			argument: request.argument,
+		};
+		let policy = pool.policy(service_name);
+		let upstream = service.as_ref().read().await;
+		match policy.read(|| upstream.complete(req.clone())).await {
+			Ok(r) => {
+				pool.succeeded(service_name);
+				Ok(r)
+			},
+			Err(e) => {
+				pool.report(service_name, &e).await;
+				Err(e.into())
+			},
+		}
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	async fn list_tools(
+		&self,
+		request: PaginatedRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<ListToolsResult, McpError> {
+		let tool_search = self.state.read().unwrap().listener.options().tool_search;
+		let (tools, next_cursor) = match tool_search {
+			true => (search::meta_tools(), None),
+			false => self.list_named_tools(request, &context).await?,
+		};
 
 		self.metrics.clone().record(
 			&metrics::ListCall {
@@ -310,10 +993,7 @@ impl ServerHandler for Relay {
 			(),
 		);
 
-		Ok(ListToolsResult {
-			tools: results.into_iter().flatten().collect(),
-			next_cursor: None,
-		})
+		Ok(ListToolsResult { tools, next_cursor })
 	}
 
 	#[instrument(
@@ -325,30 +1005,83 @@ impl ServerHandler for Relay {
   )]
 	async fn call_tool(
 		&self,
-		request: CallToolRequestParam,
-		_context: RequestContext<RoleServer>,
+		mut request: CallToolRequestParam,
+		context: RequestContext<RoleServer>,
 	) -> std::result::Result<CallToolResult, McpError> {
 		tracing::trace!("calling tool: {:?}", request);
-		if !self.state.read().unwrap().policies.validate(
+		// In tool search mode only the meta-tools are callable, and invoke_tool calls go through the usual path
+		if self.state.read().unwrap().listener.options().tool_search {
+			match request.name.as_ref() {
+				search::SEARCH_TOOLS => {
+					return self
+						.search_tools(request.arguments.as_ref(), &context)
+						.await;
+				},
+				search::INVOKE_TOOL => request = search::invocation(request.arguments)?,
+				_ => return Err(McpError::invalid_params("unknown tool", None)),
+			}
+		}
+		let (service_name, listed) = &self.resolve_tool(&request.name, &context).await?;
+		let overrides = self.tool_overrides(service_name);
+		let tool = transform::upstream_tool_name(&overrides, listed)
+			.filter(|tool| self.filters(service_name).tools.allows(tool))
+			.ok_or(McpError::invalid_params("unknown tool", None))?;
+		self.authorize_tool(service_name, tool)?;
+		self
+			.validate_arguments(&request.name, request.arguments.as_ref(), &context)
+			.await?;
+		if let Some(composite) = self.composite_tool(service_name, tool) {
+			return self
+				.call_composite(&composite, request.arguments, &context)
+				.await;
+		}
+		self
+			.call_target_tool(service_name, tool, request.arguments, &context)
+			.await
+	}
+}
+
+impl Relay {
+	/// Checks that RBAC lets the caller call a tool.
+	/// RBAC rules name tools by target and upstream name, whatever name the tool is listed under.
+	fn authorize_tool(&self, target: &str, tool: &str) -> Result<(), McpError> {
+		match self.state.read().unwrap().policies.validate(
 			&rbac::ResourceType::Tool {
-				id: request.name.to_string(),
+				id: format!("{}:{}", target, tool),
 			},
 			&self.id,
 		) {
-			return Err(McpError::invalid_request("not allowed", None));
+			true => Ok(()),
+			false => Err(McpError::invalid_request("not allowed", None)),
 		}
-		let tool_name = request.name.to_string();
-		let (service_name, tool) = tool_name
-			.split_once(':')
-			.ok_or(McpError::invalid_request("invalid tool name", None))?;
+	}
+
+	/// Calls a tool of a target, by its name on the target.
+	async fn call_target_tool(
+		&self,
+		service_name: &str,
+		tool: &str,
+		arguments: Option<JsonObject>,
+		context: &RequestContext<RoleServer>,
+	) -> Result<CallToolResult, McpError> {
+		let overrides = self.tool_overrides(service_name);
 		let pool = self.pool.read().await;
-		let service = pool
-			.get(service_name)
-			.await
-			.ok_or(McpError::invalid_request("invalid service name", None))?;
+		let service = match pool.acquire(service_name).await {
+			Ok(service) => service,
+			Err(e) => {
+				self.record_tool_error(service_name, tool, &e);
+				return Err(e.into());
+			},
+		};
+		let o = overrides.iter().find(|o| o.name == tool);
+		let arguments = match o {
+			Some(o) => transform::inject_arguments(o, arguments, &self.id)
+				.map_err(|e| McpError::invalid_params(e, None))?,
+			None => arguments,
+		};
 		let req = CallToolRequestParam {
 			name: Cow::Owned(tool.to_string()),
-			arguments: request.arguments,
+			arguments,
 		};
 
 		self.metrics.clone().record(
@@ -359,19 +1092,136 @@ impl ServerHandler for Relay {
 			(),
 		);
 
-		match service.as_ref().read().await.call_tool(req).await {
-			Ok(r) => Ok(r),
-			Err(e) => {
-				self.metrics.clone().record(
-					&metrics::ToolCallError {
-						server: service_name.to_string(),
-						name: tool.to_string(),
-						error_type: e.error_code(),
-					},
-					(),
-				);
-				Err(e.into())
+		let cache = o.and_then(|o| o.cache.as_ref()).map(|config| {
+			let (tool_cache, generation) = {
+				let state = self.state.read().unwrap();
+				(
+					state.tool_cache.clone(),
+					state.targets.generation(service_name),
+				)
+			};
+			let call = cache::ToolCall::new(
+				service_name,
+				tool,
+				req.arguments.as_ref(),
+				config.per_identity.then_some(&self.id),
+			);
+			(tool_cache, generation, config, call)
+		});
+		if let Some((tool_cache, generation, _, call)) = &cache {
+			if let Some(result) = tool_cache.get(call, *generation) {
+				self.metrics.clone().record(
+					&metrics::ToolCacheHit {
+						server: service_name.to_string(),
+						name: tool.to_string(),
+					},
+					(),
+				);
+				return Ok(result);
+			}
+			self.metrics.clone().record(
+				&metrics::ToolCacheMiss {
+					server: service_name.to_string(),
+					name: tool.to_string(),
+				},
+				(),
+			);
+		}
+
+		let policy = pool.policy(service_name);
+		let upstream = service.as_ref().read().await;
+		match policy
+			.call(|| upstream.call_tool(req.clone(), &context.ct))
+			.await
+		{
+			Ok(r) => {
+				pool.succeeded(service_name);
+				// Errors returned by the tool may be transient, so only successful results are cached
+				if let Some((tool_cache, generation, config, call)) = &cache {
+					if r.is_error != Some(true) {
+						tool_cache.insert(call, *generation, config, r.clone());
+					}
+				}
+				Ok(r)
+			},
+			Err(e) => {
+				self.record_tool_error(service_name, tool, &e);
+				pool.report(service_name, &e).await;
+				Err(e.into())
+			},
+		}
+	}
+
+	fn record_tool_error(&self, service_name: &str, tool: &str, error: &UpstreamError) {
+		self.metrics.clone().record(
+			&metrics::ToolCallError {
+				server: service_name.to_string(),
+				name: tool.to_string(),
+				error_type: error.error_code(),
+			},
+			(),
+		);
+	}
+
+	fn composite_tool(&self, target: &str, tool: &str) -> Option<CompositeTool> {
+		match &self.state.read().unwrap().targets.get(target)?.spec {
+			TargetSpec::Composite { tools } => tools.iter().find(|t| t.name == tool).cloned(),
+			_ => None,
+		}
+	}
+
+	/// Runs the steps of a composite tool in order, each calling a tool of another target.
+	/// Steps are checked against filters and RBAC like any tool call, but may call hidden tools.
+	async fn call_composite(
+		&self,
+		tool: &CompositeTool,
+		arguments: Option<JsonObject>,
+		context: &RequestContext<RoleServer>,
+	) -> Result<CallToolResult, McpError> {
+		let mut scope = composite::Scope::new(arguments);
+		let mut result = None;
+		for step in &tool.steps {
+			if self.composite_tool(&step.target, &step.tool).is_some() {
+				return Err(McpError::internal_error(
+					format!("step {} of {} calls a composite tool", step.id, tool.name),
+					None,
+				));
+			}
+			if !self.filters(&step.target).tools.allows(&step.tool) {
+				return Err(McpError::internal_error(
+					format!("step {} of {} calls an unknown tool", step.id, tool.name),
+					None,
+				));
+			}
+			self.authorize_tool(&step.target, &step.tool)?;
+			let arguments = match scope.render(&Value::Object(step.arguments.clone())) {
+				Ok(Value::Object(arguments)) => arguments,
+				Ok(_) => JsonObject::new(),
+				Err(e) => {
+					return Err(McpError::invalid_params(
+						format!("step {} of {}: {}", step.id, tool.name, e),
+						None,
+					));
+				},
+			};
+			let r = self
+				.call_target_tool(&step.target, &step.tool, Some(arguments), context)
+				.await?;
+			// A step that failed ends the tool, with the error of the step
+			if r.is_error == Some(true) {
+				return Ok(r);
+			}
+			scope.insert_step(&step.id, &r);
+			result = Some(r);
+		}
+		match &tool.output {
+			Some(output) => {
+				composite::output(&scope, output).map_err(|e| McpError::internal_error(e, None))
 			},
+			None => Ok(result.unwrap_or(CallToolResult {
+				content: vec![],
+				is_error: None,
+			})),
 		}
 	}
 }
@@ -379,69 +1229,304 @@ impl ServerHandler for Relay {
 #[derive(Clone)]
 struct ConnectionPool {
 	state: Arc<std::sync::RwLock<XdsStore>>,
+	metrics: Arc<metrics::Metrics>,
 
 	by_name: Arc<RwLock<HashMap<String, Arc<RwLock<UpstreamTarget>>>>>,
+	// The endpoint each target connection was balanced to
+	endpoints: Arc<std::sync::Mutex<HashMap<String, EndpointHandle>>>,
+	downstream: Downstream,
 }
 
 impl ConnectionPool {
-	fn new(state: Arc<std::sync::RwLock<XdsStore>>) -> Self {
+	fn new(
+		state: Arc<std::sync::RwLock<XdsStore>>,
+		metrics: Arc<metrics::Metrics>,
+		downstream: Downstream,
+	) -> Self {
 		Self {
 			state,
+			metrics,
 			by_name: Arc::new(RwLock::new(HashMap::new())),
+			endpoints: Arc::new(std::sync::Mutex::new(HashMap::new())),
+			downstream,
 		}
 	}
 
 	async fn get(&self, name: &str) -> Option<Arc<RwLock<UpstreamTarget>>> {
 		tracing::trace!("getting connection for target: {}", name);
-		let by_name = self.by_name.read().await;
-		match by_name.get(name) {
-			Some(connection) => {
-				tracing::trace!("connection found for target: {}", name);
-				Some(connection.clone())
+		let existing = { self.by_name.read().await.get(name).cloned() };
+		if let Some(connection) = existing {
+			let closed = { connection.read().await.closed_reason() };
+			match closed {
+				None => {
+					tracing::trace!("connection found for target: {}", name);
+					return Some(connection);
+				},
+				Some(reason) => self.evict(name, reason).await,
+			}
+		}
+
+		let target = { self.state.read().unwrap().targets.get(name).cloned() };
+		let Some(target) = target else {
+			tracing::error!("Target not found: {}", name);
+			// Need to demand it, but this should never happen
+			return None;
+		};
+		if !self.state.read().unwrap().upstreams.should_connect(name) {
+			tracing::debug!("target {} is backing off, not connecting", name);
+			return None;
+		}
+		self.set_state(name, |upstreams| upstreams.connecting(name));
+		let connection = match target.timeouts.connect {
+			Some(timeout) => tokio::time::timeout(timeout, self.connect(&target))
+				.await
+				.unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", timeout))),
+			None => self.connect(&target).await,
+		};
+		match connection {
+			Ok(connection) => {
+				self.set_state(name, |upstreams| upstreams.ready(name));
+				Some(connection)
 			},
-			None => {
-				let target = { self.state.read().unwrap().targets.get(name).cloned() };
-				match target {
-					Some(target) => {
-						// We want write access to the by_name map, so we drop the read lock
-						// TODO: Fix this
-						drop(by_name);
-						match self.connect(&target).await {
-							Ok(connection) => Some(connection),
-							Err(e) => {
-								tracing::error!("Error connecting to target: {}", e);
-								None
-							},
-						}
-					},
-					None => {
-						tracing::error!("Target not found: {}", name);
-						// Need to demand it, but this should never happen
-						None
-					},
-				}
+			Err(e) => {
+				tracing::error!("Error connecting to target: {}", e);
+				self.eject_endpoint(name);
+				self.set_state(name, |upstreams| upstreams.failed(name, e.to_string()));
+				None
 			},
 		}
 	}
 
-	async fn iter(&self) -> impl Iterator<Item = (String, Arc<RwLock<UpstreamTarget>>)> {
-		// Iterate through all state targets, and get the connection from the pool
+	async fn iter(
+		&self,
+		include: impl Fn(&str) -> bool,
+	) -> impl Iterator<Item = (String, Result<Arc<RwLock<UpstreamTarget>>, UpstreamError>)> {
+		// Iterate through the included state targets, and get the connection from the pool
 		// If the connection is not in the pool, connect to it and add it to the pool
+		// Targets which cannot be connected to are returned with the reason, so they are reported
+		// Targets are filtered before they are admitted, so excluded ones do not use up a half-open probe
 		let targets: Vec<(String, Target)> = {
 			let state = self.state.read().unwrap();
 			state
 				.targets
 				.iter()
+				.filter(|(name, _)| include(name))
 				.map(|(name, target)| (name.clone(), target.clone()))
 				.collect()
 		};
-		let x = targets.iter().map(|(name, _target)| async move {
-			let connection = self.get(name).await.unwrap();
-			(name.clone(), connection)
-		});
+		let x = targets
+			.iter()
+			.map(|(name, _target)| async move { (name.clone(), self.acquire(name).await) });
+
+		futures::future::join_all(x).await.into_iter()
+	}
+
+	/// Returns the connection to a target, connecting to it if needed.
+	/// Fails fast while its circuit breaker is open, and with the last connection error while it backs off.
+	async fn acquire(&self, name: &str) -> Result<Arc<RwLock<UpstreamTarget>>, UpstreamError> {
+		if !self.admit(name) {
+			return Err(UpstreamError::CircuitOpen);
+		}
+		match self.get(name).await {
+			Some(connection) => Ok(connection),
+			None => {
+				let state = self.state.read().unwrap();
+				Err(UpstreamError::Unavailable(state.upstreams.last_error(name)))
+			},
+		}
+	}
+
+	/// Connects to a target only to record its instructions, then disconnects.
+	async fn fetch_instructions(&self, name: &str) -> bool {
+		let Some(connection) = self.get(name).await else {
+			return false;
+		};
+		self.by_name.write().await.remove(name);
+		// The pool held the only other reference to the connection
+		if let Ok(connection) = Arc::try_unwrap(connection) {
+			connection.into_inner().close().await;
+		}
+		true
+	}
+
+	/// Returns the targets this session is connected to, without connecting to the others.
+	async fn connected(&self) -> Vec<(String, Arc<RwLock<UpstreamTarget>>)> {
+		self
+			.by_name
+			.read()
+			.await
+			.iter()
+			.map(|(name, connection)| (name.clone(), connection.clone()))
+			.collect()
+	}
+
+	fn policy(&self, name: &str) -> RequestPolicy {
+		// The state lock is released before the endpoints lock is taken, as pick_endpoint does,
+		// so the two are never held together
+		let target = {
+			let state = self.state.read().unwrap();
+			state
+				.targets
+				.get(name)
+				.map(|target| (target.timeouts.clone(), target.retries.clone()))
+		};
+		match target {
+			Some((timeouts, retries)) => RequestPolicy {
+				timeouts,
+				retries,
+				endpoint: self.endpoints.lock().unwrap().get(name).cloned(),
+			},
+			None => RequestPolicy::default(),
+		}
+	}
+
+	/// Returns false if the circuit breaker of a target is open, so requests to it should fail fast.
+	fn admit(&self, name: &str) -> bool {
+		// A closed breaker admits every request, without taking the write lock
+		let breaker = { self.state.read().unwrap().upstreams.breaker(name) };
+		if breaker.is_none_or(|breaker| breaker == BreakerState::Closed) {
+			return true;
+		}
+		self
+			.update_breaker(name, |upstreams, breaker| upstreams.admit(name, breaker))
+			.unwrap_or(true)
+	}
+
+	fn succeeded(&self, name: &str) {
+		self.update_breaker(name, |upstreams, _| upstreams.request_succeeded(name));
+	}
+
+	/// Records a failed request against the circuit breaker of a target, and evicts the
+	/// connection to it if the error shows the connection itself is broken, so the next request reconnects.
+	async fn report(&self, name: &str, error: &UpstreamError) {
+		if error.is_failure() {
+			self.update_breaker(name, |upstreams, breaker| {
+				upstreams.request_failed(name, breaker)
+			});
+		}
+		if error.is_transport() {
+			self.evict(name, error.to_string()).await;
+		}
+	}
+
+	/// Records the outcome of a request to several targets.
+	/// Targets that were not contacted, as their results were cached, are left out.
+	async fn report_all<T>(&self, results: &[(String, bool, T)], errors: &[(String, UpstreamError)]) {
+		for (name, _, _) in results.iter().filter(|(_, contacted, _)| *contacted) {
+			self.succeeded(name);
+		}
+		for (name, error) in errors {
+			self.report(name, error).await;
+		}
+	}
+
+	/// Updates the circuit breaker of a target, if it has one, and records its state when it changes.
+	fn update_breaker<T>(
+		&self,
+		name: &str,
+		update: impl FnOnce(&mut UpstreamStore, &CircuitBreaker) -> T,
+	) -> Option<T> {
+		// Most targets have no breaker, so only those that do take the write lock
+		let breaker = {
+			let state = self.state.read().unwrap();
+			state.targets.get(name)?.circuit_breaker.clone()?
+		};
+		let (result, before, after) = {
+			let mut state = self.state.write().unwrap();
+			let before = state.upstreams.breaker(name);
+			let result = update(&mut state.upstreams, &breaker);
+			(result, before, state.upstreams.breaker(name))
+		};
+		if let Some(state) = after.filter(|after| Some(*after) != before) {
+			self.metrics.record(
+				&metrics::CircuitBreakerState {
+					server: name.to_string(),
+					state: state.as_str().to_string(),
+				},
+				(),
+			);
+		}
+		Some(result)
+	}
+
+	async fn evict(&self, name: &str, reason: String) {
+		let removed = { self.by_name.write().await.remove(name) };
+		if removed.is_none() {
+			// Another request already evicted it
+			return;
+		}
+		tracing::warn!("connection to target {} lost: {}", name, reason);
+		self.eject_endpoint(name);
+		// A restarted target may list something else
+		self.downstream.lists().invalidate_target(name);
+		self.metrics.record(
+			&metrics::UpstreamRestart {
+				server: name.to_string(),
+			},
+			(),
+		);
+		self.set_state(name, |upstreams| upstreams.lost(name, reason));
+	}
+
+	fn client(&self, target: &str) -> UpstreamClient {
+		UpstreamClient::new(target, self.downstream.clone())
+	}
+
+	/// Picks the endpoint to connect to, preferring the one this session used before.
+	fn pick_endpoint(
+		&self,
+		name: &str,
+		addresses: Vec<Address>,
+		strategy: &LoadBalancing,
+	) -> Option<EndpointHandle> {
+		let affinity = {
+			let endpoints = self.endpoints.lock().unwrap();
+			endpoints.get(name).map(|e| e.address.clone())
+		};
+		let picked = {
+			let mut state = self.state.write().unwrap();
+			let balancer = state.upstreams.balancer(name);
+			balancer.update(addresses);
+			balancer.pick(strategy, affinity.as_ref())
+		}?;
+		self
+			.endpoints
+			.lock()
+			.unwrap()
+			.insert(name.to_string(), picked.clone());
+		Some(picked)
+	}
+
+	/// Takes the endpoint of a failed connection out of rotation for every session.
+	fn eject_endpoint(&self, name: &str) {
+		let Some(endpoint) = self.endpoints.lock().unwrap().get(name).cloned() else {
+			return;
+		};
+		tracing::debug!("ejecting endpoint {} of target {}", endpoint.address, name);
+		self
+			.state
+			.write()
+			.unwrap()
+			.upstreams
+			.balancer(name)
+			.eject(&endpoint.address);
+	}
 
-		let x = futures::future::join_all(x).await;
-		x.into_iter()
+	fn set_state(&self, name: &str, update: impl FnOnce(&mut UpstreamStore)) {
+		let state = {
+			let mut state = self.state.write().unwrap();
+			update(&mut state.upstreams);
+			state.upstreams.state(name)
+		};
+		if let Some(state) = state {
+			self.metrics.record(
+				&metrics::UpstreamState {
+					server: name.to_string(),
+					state: state.as_str().to_string(),
+				},
+				(),
+			);
+		}
 	}
 
 	#[instrument(
@@ -459,48 +1544,91 @@ impl ConnectionPool {
 				port,
 				path,
 				backend_auth,
+				endpoints,
+				dns_discovery,
+				load_balancing,
 			} => {
 				tracing::trace!("starting sse transport for target: {}", target.name);
 				let path = match path.as_str() {
 					"" => "/sse",
 					_ => path,
 				};
+				let mut all = vec![Endpoint {
+					host: host.clone(),
+					port: *port,
+				}];
+				all.extend(endpoints.iter().cloned());
+				let addresses = balancer::resolve(&all, *dns_discovery).await?;
+				let endpoint = self
+					.pick_endpoint(&target.name, addresses, load_balancing)
+					.ok_or_else(|| anyhow::anyhow!("no endpoints available"))?;
+				tracing::debug!("connecting to endpoint {}", endpoint.address);
+				let Address { host, port, ip } = endpoint.address.clone();
 				let scheme = match port {
 					443 => "https",
 					_ => "http",
 				};
 
 				let url = format!("{}://{}:{}{}", scheme, host, port, path);
-				let transport = match backend_auth.clone() {
-					Some(backend_auth) => {
-						let backend_auth = backend_auth.build().await;
-						let token = backend_auth.get_token().await?;
-						let mut headers = HeaderMap::new();
-						let auth_value = HeaderValue::from_str(token.as_str()).unwrap();
-						headers.insert(AUTHORIZATION, auth_value);
-						let client = reqwest::Client::builder()
-							.default_headers(headers)
-							.build()
-							.unwrap();
-						let client = ReqwestSseClient::new_with_client(url.as_str(), client).await?;
-						SseTransport::start_with_client(client).await?
-					},
-					None => {
-						let client = ReqwestSseClient::new(url.as_str())?;
-						SseTransport::start_with_client(client).await?
-					},
-				};
+				let mut client = reqwest::Client::builder();
+				if let Some(ip) = ip {
+					// Pin the host to the discovered address, so TLS still verifies the host name
+					client = client.resolve(&host, SocketAddr::new(ip, port as u16));
+				}
+				if let Some(backend_auth) = backend_auth.clone() {
+					let backend_auth = backend_auth.build().await;
+					let token = backend_auth.get_token().await?;
+					let mut headers = HeaderMap::new();
+					let auth_value = HeaderValue::from_str(token.as_str()).unwrap();
+					headers.insert(AUTHORIZATION, auth_value);
+					client = client.default_headers(headers);
+				}
+				let client = ReqwestSseClient::new_with_client(url.as_str(), client.build()?).await?;
+				let transport = SseTransport::start_with_client(client).await?;
 
-				UpstreamTarget::Mcp(serve_client((), transport).await?)
+				UpstreamTarget::Mcp(
+					serve_client(self.client(&target.name), transport).await?,
+					endpoint.open_connection(),
+				)
 			},
-			TargetSpec::Stdio { cmd, args, env: _ } => {
+			TargetSpec::Stdio {
+				cmd,
+				args,
+				env,
+				cwd,
+				clear_env,
+				inherit_env,
+				limits,
+				stderr_level,
+			} => {
 				tracing::trace!("starting stdio transport for target: {}", target.name);
-				UpstreamTarget::Mcp(
-					serve_client(
-						(),
-						TokioChildProcess::new(Command::new(cmd).args(args)).unwrap(),
-					)
-					.await?,
+				let mut command = Command::new(cmd);
+				command.args(args);
+				if *clear_env {
+					command.env_clear();
+					command.envs(
+						inherit_env
+							.iter()
+							.filter_map(|key| std::env::var_os(key).map(|value| (key, value))),
+					);
+				}
+				command.envs(env);
+				if let Some(cwd) = cwd {
+					command.current_dir(cwd);
+				}
+				let stderr = stdio::Stderr {
+					target: target.name.clone(),
+					level: stderr_level
+						.as_deref()
+						.and_then(|level| level.parse().ok())
+						.unwrap_or(tracing::Level::INFO),
+					buffer: self.state.write().unwrap().upstreams.stderr(&target.name),
+				};
+				let (process, transport) = stdio::Process::spawn(&mut command, limits.as_ref(), stderr)?;
+				tracing::debug!(pid = ?process.id(), "spawned stdio target: {}", target.name);
+				UpstreamTarget::Stdio(
+					serve_client(self.client(&target.name), transport).await?,
+					process,
 				)
 			},
 			TargetSpec::OpenAPI { host, port, schema } => {
@@ -513,7 +1641,27 @@ impl ConnectionPool {
 					schema: schema.clone(),
 				})
 			},
+			TargetSpec::Composite { tools } => UpstreamTarget::Composite(tools.clone()),
+			TargetSpec::Static { prompts, resources } => UpstreamTarget::Static(StaticHandler {
+				prompts: prompts.clone(),
+				resources: resources.clone(),
+			}),
 		};
+		self
+			.state
+			.write()
+			.unwrap()
+			.upstreams
+			.set_instructions(&target.name, transport.instructions());
+		if let Some(level) = self.downstream.level() {
+			if let Err(e) = transport.set_level(level).await {
+				tracing::debug!(
+					"failed to set the log level of target {}: {}",
+					target.name,
+					e
+				);
+			}
+		}
 		let connection = Arc::new(RwLock::new(transport));
 		// We need to drop this lock quick
 		let mut by_name = self.by_name.write().await;
@@ -522,19 +1670,208 @@ impl ConnectionPool {
 	}
 }
 
+/// Fetches the instructions of each MCP target when it is inserted or updated, if the listener composes
+/// instructions, so clients get those of targets no session has connected to yet.
+/// Targets that cannot be connected to are fetched again on the next update.
+pub async fn fetch_instructions(
+	state: Arc<std::sync::RwLock<XdsStore>>,
+	metrics: Arc<metrics::Metrics>,
+) {
+	let updated = state.read().unwrap().targets.updated();
+	let downstream = Downstream::new(state.clone(), rbac::Identity::empty());
+	let pool = ConnectionPool::new(state.clone(), metrics, downstream);
+	let mut fetched: HashMap<String, u64> = HashMap::new();
+	loop {
+		let pending: Vec<(String, u64)> = {
+			let state = state.read().unwrap();
+			if !state.listener.options().compose_instructions {
+				return;
+			}
+			state
+				.targets
+				.iter()
+				.filter(|(_, target)| {
+					matches!(
+						target.spec,
+						TargetSpec::Sse { .. } | TargetSpec::Stdio { .. }
+					)
+				})
+				.map(|(name, _)| {
+					let generation = state.targets.generation(&name);
+					(name, generation)
+				})
+				.filter(|(name, generation)| fetched.get(name) != Some(generation))
+				.collect()
+		};
+		let results = futures::future::join_all(
+			pending
+				.iter()
+				.map(|(name, _)| pool.fetch_instructions(name)),
+		)
+		.await;
+		for ((name, generation), ok) in pending.into_iter().zip(results) {
+			if ok {
+				fetched.insert(name, generation);
+			}
+		}
+		updated.notified().await;
+	}
+}
+
+// The backoff between retries doubles up to this, however many attempts a target allows
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// RequestPolicy applies the timeouts and retries of a target to the requests sent to it.
+#[derive(Clone, Debug, Default)]
+struct RequestPolicy {
+	timeouts: Timeouts,
+	retries: Option<RetryPolicy>,
+	endpoint: Option<EndpointHandle>,
+}
+
+impl RequestPolicy {
+	async fn list<T, F, Fut>(&self, f: F) -> Result<T, UpstreamError>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, UpstreamError>>,
+	{
+		self.run(self.timeouts.list, true, f).await
+	}
+
+	async fn read<T, F, Fut>(&self, f: F) -> Result<T, UpstreamError>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, UpstreamError>>,
+	{
+		self.run(self.timeouts.call, true, f).await
+	}
+
+	/// Tool calls may have side effects, so they are never retried.
+	async fn call<T, F, Fut>(&self, f: F) -> Result<T, UpstreamError>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, UpstreamError>>,
+	{
+		self.run(self.timeouts.call, false, f).await
+	}
+
+	async fn run<T, F, Fut>(
+		&self,
+		timeout: Option<Duration>,
+		idempotent: bool,
+		f: F,
+	) -> Result<T, UpstreamError>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, UpstreamError>>,
+	{
+		let _request = self.endpoint.as_ref().map(|e| e.start_request());
+		let retries = self.retries.as_ref().filter(|_| idempotent);
+		let attempts = retries.map(|r| r.attempts).unwrap_or(1);
+		let mut attempt = 1;
+		loop {
+			let result = match timeout {
+				Some(timeout) => tokio::time::timeout(timeout, f())
+					.await
+					.unwrap_or_else(|_| Err(UpstreamError::Timeout(timeout))),
+				None => f().await,
+			};
+			match (result, retries) {
+				(Err(e), Some(retries)) if attempt < attempts && e.is_retryable() => {
+					let backoff = retries
+						.backoff
+						.checked_mul(2u32.saturating_pow(attempt - 1))
+						.map_or(MAX_RETRY_BACKOFF, |backoff| backoff.min(MAX_RETRY_BACKOFF));
+					tracing::debug!(
+						"attempt {} failed: {}, retrying in {:?}",
+						attempt,
+						e,
+						backoff
+					);
+					tokio::time::sleep(backoff).await;
+					attempt += 1;
+				},
+				(result, _) => return result,
+			}
+		}
+	}
+}
+
 /// UpstreamTarget defines a source for MCP information.
 #[derive(Debug)]
 enum UpstreamTarget {
-	Mcp(RunningService<RoleClient, ()>),
+	// The guard counts the connection against its endpoint for as long as it is open
+	Mcp(RunningService<RoleClient, UpstreamClient>, CountGuard),
+	Stdio(RunningService<RoleClient, UpstreamClient>, stdio::Process),
 	OpenAPI(OpenAPIHandler),
+	// Composite tools are run by the relay, so the target only lists them
+	Composite(Vec<CompositeTool>),
+	Static(StaticHandler),
+}
+
+impl UpstreamTarget {
+	/// Returns why the upstream can no longer serve requests, if it can't.
+	/// Only a stdio process that exited is detected here: rmcp does not expose whether an SSE session
+	/// closed, so a closed session is only noticed by the transport error of the next request to it.
+	/// Ends the session with the target. Stdio processes are killed once dropped.
+	async fn close(self) {
+		if let UpstreamTarget::Mcp(service, _) | UpstreamTarget::Stdio(service, _) = self {
+			if let Err(e) = service.cancel().await {
+				tracing::debug!("failed to close the connection to a target: {}", e);
+			}
+		}
+	}
+
+	fn closed_reason(&self) -> Option<String> {
+		match self {
+			UpstreamTarget::Stdio(_, process) => process.exited(),
+			UpstreamTarget::Mcp(..)
+			| UpstreamTarget::OpenAPI(_)
+			| UpstreamTarget::Composite(_)
+			| UpstreamTarget::Static(_) => None,
+		}
+	}
 }
 
 enum UpstreamError {
 	ServiceError(rmcp::ServiceError),
 	OpenAPIError(anyhow::Error),
+	Timeout(Duration),
+	CircuitOpen,
+	// The target could not be connected to, or is backing off after failing to, with the last error
+	Unavailable(Option<String>),
 }
 
 impl UpstreamError {
+	fn is_transport(&self) -> bool {
+		matches!(self, Self::ServiceError(rmcp::ServiceError::Transport(_)))
+	}
+
+	/// Returns whether the error counts against the circuit breaker of the target.
+	/// Errors returned by the target itself show that it is up.
+	fn is_failure(&self) -> bool {
+		!matches!(
+			self,
+			Self::ServiceError(rmcp::ServiceError::McpError(_))
+				| Self::ServiceError(rmcp::ServiceError::Cancelled { .. })
+				| Self::CircuitOpen
+				| Self::Unavailable(_)
+		)
+	}
+
+	fn is_timeout(&self) -> bool {
+		matches!(
+			self,
+			Self::Timeout(_) | Self::ServiceError(rmcp::ServiceError::Timeout { .. })
+		)
+	}
+
+	/// Returns whether an idempotent request that failed with the error may succeed if retried.
+	/// Errors returned by the target itself are final.
+	fn is_retryable(&self) -> bool {
+		self.is_timeout() || self.is_transport()
+	}
+
 	fn error_code(&self) -> String {
 		match self {
 			Self::ServiceError(e) => match e {
@@ -548,9 +1885,25 @@ impl UpstreamError {
 				_ => "unknown".to_string(),
 			},
 			Self::OpenAPIError(_) => "openapi_error".to_string(),
+			Self::Timeout(_) => "timeout".to_string(),
+			Self::CircuitOpen => "circuit_open".to_string(),
+			Self::Unavailable(_) => "unavailable".to_string(),
 		}
 	}
 }
+impl std::fmt::Display for UpstreamError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::ServiceError(e) => write!(f, "{}", e),
+			Self::OpenAPIError(e) => write!(f, "{}", e),
+			Self::Timeout(timeout) => write!(f, "request timed out after {:?}", timeout),
+			Self::CircuitOpen => write!(f, "circuit breaker is open"),
+			Self::Unavailable(Some(e)) => write!(f, "target is unavailable: {}", e),
+			Self::Unavailable(None) => write!(f, "target is unavailable"),
+		}
+	}
+}
+
 impl From<rmcp::ServiceError> for UpstreamError {
 	fn from(value: rmcp::ServiceError) -> Self {
 		UpstreamError::ServiceError(value)
@@ -567,6 +1920,13 @@ impl From<UpstreamError> for ErrorData {
 	fn from(value: UpstreamError) -> Self {
 		match value {
 			UpstreamError::OpenAPIError(e) => ErrorData::internal_error(e.to_string(), None),
+			UpstreamError::Timeout(timeout) => {
+				ErrorData::internal_error(format!("request timed out after {:?}", timeout), None)
+			},
+			UpstreamError::CircuitOpen => {
+				ErrorData::internal_error("target is unavailable: circuit breaker is open", None)
+			},
+			UpstreamError::Unavailable(_) => ErrorData::internal_error(value.to_string(), None),
 			UpstreamError::ServiceError(e) => match e {
 				rmcp::ServiceError::McpError(e) => e,
 				rmcp::ServiceError::Timeout { timeout } => {
@@ -592,11 +1952,34 @@ impl UpstreamTarget {
 		request: PaginatedRequestParam,
 	) -> Result<ListToolsResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.list_tools(request).await?),
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(m.list_tools(request).await?),
 			UpstreamTarget::OpenAPI(m) => Ok(ListToolsResult {
 				next_cursor: None,
 				tools: m.tools(),
 			}),
+			UpstreamTarget::Composite(tools) => Ok(ListToolsResult {
+				next_cursor: None,
+				tools: composite::list_tools(tools),
+			}),
+			UpstreamTarget::Static(_) => Ok(ListToolsResult {
+				next_cursor: None,
+				tools: vec![],
+			}),
+		}
+	}
+
+	async fn complete(&self, request: CompleteRequestParam) -> Result<CompleteResult, UpstreamError> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(m.complete(request).await?),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Ok(CompleteResult {
+					completion: CompletionInfo {
+						values: vec![],
+						total: None,
+						has_more: None,
+					},
+				})
+			},
 		}
 	}
 
@@ -605,8 +1988,9 @@ impl UpstreamTarget {
 		request: GetPromptRequestParam,
 	) -> Result<GetPromptResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.get_prompt(request).await?),
-			UpstreamTarget::OpenAPI(_) => Ok(GetPromptResult {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(m.get_prompt(request).await?),
+			UpstreamTarget::Static(s) => s.get_prompt(&request.name, request.arguments),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) => Ok(GetPromptResult {
 				description: None,
 				messages: vec![],
 			}),
@@ -618,8 +2002,12 @@ impl UpstreamTarget {
 		request: PaginatedRequestParam,
 	) -> Result<ListPromptsResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.list_prompts(request).await?),
-			UpstreamTarget::OpenAPI(_) => Ok(ListPromptsResult {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(m.list_prompts(request).await?),
+			UpstreamTarget::Static(s) => Ok(ListPromptsResult {
+				next_cursor: None,
+				prompts: s.list_prompts(),
+			}),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) => Ok(ListPromptsResult {
 				next_cursor: None,
 				prompts: vec![],
 			}),
@@ -631,8 +2019,14 @@ impl UpstreamTarget {
 		request: PaginatedRequestParam,
 	) -> Result<ListResourcesResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.list_resources(request).await?),
-			UpstreamTarget::OpenAPI(_) => Ok(ListResourcesResult {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				Ok(m.list_resources(request).await?)
+			},
+			UpstreamTarget::Static(s) => Ok(ListResourcesResult {
+				next_cursor: None,
+				resources: s.list_resources().await?,
+			}),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) => Ok(ListResourcesResult {
 				next_cursor: None,
 				resources: vec![],
 			}),
@@ -644,11 +2038,15 @@ impl UpstreamTarget {
 		request: PaginatedRequestParam,
 	) -> Result<ListResourceTemplatesResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.list_resource_templates(request).await?),
-			UpstreamTarget::OpenAPI(_) => Ok(ListResourceTemplatesResult {
-				next_cursor: None,
-				resource_templates: vec![],
-			}),
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				Ok(m.list_resource_templates(request).await?)
+			},
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Ok(ListResourceTemplatesResult {
+					next_cursor: None,
+					resource_templates: vec![],
+				})
+			},
 		}
 	}
 
@@ -657,30 +2055,175 @@ impl UpstreamTarget {
 		request: ReadResourceRequestParam,
 	) -> Result<ReadResourceResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.read_resource(request).await?),
-			UpstreamTarget::OpenAPI(_) => Ok(ReadResourceResult { contents: vec![] }),
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				Ok(m.read_resource(request).await?)
+			},
+			UpstreamTarget::Static(s) => s.read_resource(&request.uri).await,
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) => {
+				Ok(ReadResourceResult { contents: vec![] })
+			},
+		}
+	}
+
+	async fn subscribe(&self, uri: &str) -> Result<(), UpstreamError> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(
+				m.subscribe(SubscribeRequestParam {
+					uri: uri.to_string(),
+				})
+				.await?,
+			),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Err(UpstreamError::ServiceError(rmcp::ServiceError::McpError(
+					McpError::invalid_request("target does not support subscriptions", None),
+				)))
+			},
+		}
+	}
+
+	async fn unsubscribe(&self, uri: &str) -> Result<(), UpstreamError> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => Ok(
+				m.unsubscribe(UnsubscribeRequestParam {
+					uri: uri.to_string(),
+				})
+				.await?,
+			),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Ok(())
+			},
+		}
+	}
+
+	/// Returns the instructions the target sent when it was initialized.
+	fn instructions(&self) -> Option<String> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => m.peer_info().instructions.clone(),
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => None,
+		}
+	}
+
+	async fn set_level(&self, level: LoggingLevel) -> Result<(), UpstreamError> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				Ok(m.set_level(SetLevelRequestParam { level }).await?)
+			},
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Ok(())
+			},
+		}
+	}
+
+	async fn notify_roots_list_changed(&self) -> Result<(), UpstreamError> {
+		match self {
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				Ok(m.notify_roots_list_changed().await?)
+			},
+			UpstreamTarget::OpenAPI(_) | UpstreamTarget::Composite(_) | UpstreamTarget::Static(_) => {
+				Ok(())
+			},
 		}
 	}
 
+	/// Calls a tool, until the call completes or the downstream request is cancelled.
+	/// A call cancelled by the client, or timed out, is cancelled on the target as well, using the id of the upstream request.
 	async fn call_tool(
 		&self,
 		request: CallToolRequestParam,
+		ct: &CancellationToken,
 	) -> Result<CallToolResult, UpstreamError> {
 		match self {
-			UpstreamTarget::Mcp(m) => Ok(m.call_tool(request).await?),
-			UpstreamTarget::OpenAPI(m) => {
-				let res = m
-					.call_tool(request.name.as_ref(), request.arguments)
+			UpstreamTarget::Mcp(m, _) | UpstreamTarget::Stdio(m, _) => {
+				// Only cancellation is relayed. The request carries no progress token, as rmcp has no `_meta`
+				// on requests, so the target sends no progress for it
+				let handle = m
+					.send_cancellable_request(
+						ClientRequest::CallToolRequest(Request {
+							method: Default::default(),
+							params: request,
+						}),
+						PeerRequestOptions::no_options(),
+					)
 					.await?;
+				// If the call is dropped before it completes, as it is when the request policy times it out,
+				// the guard cancels it on the target
+				let mut guard = CancelOnDrop {
+					peer: m.peer().clone(),
+					id: Some(handle.id.clone()),
+					reason: "timed out",
+				};
+				let response = tokio::select! {
+					response = handle.await_response() => response,
+					_ = ct.cancelled() => {
+						guard.reason = "cancelled by the client";
+						return Err(rmcp::ServiceError::Cancelled {
+							reason: Some(guard.reason.to_string()),
+						}
+						.into());
+					},
+				};
+				guard.id = None;
+				match response? {
+					ServerResult::CallToolResult(result) => Ok(result),
+					_ => Err(rmcp::ServiceError::UnexpectedResponse.into()),
+				}
+			},
+			UpstreamTarget::OpenAPI(m) => {
+				let res = tokio::select! {
+					res = m.call_tool(request.name.as_ref(), request.arguments) => res?,
+					_ = ct.cancelled() => {
+						return Err(rmcp::ServiceError::Cancelled {
+							reason: Some("cancelled by the client".to_string()),
+						}
+						.into());
+					},
+				};
 				Ok(CallToolResult {
 					content: vec![Content::text(res)],
 					is_error: None,
 				})
 			},
+			UpstreamTarget::Composite(_) => {
+				Err(UpstreamError::ServiceError(rmcp::ServiceError::McpError(
+					McpError::internal_error("composite tools are run by the relay", None),
+				)))
+			},
+			UpstreamTarget::Static(_) => Err(UpstreamError::ServiceError(rmcp::ServiceError::McpError(
+				McpError::invalid_params("unknown tool", None),
+			))),
 		}
 	}
 }
 
+/// CancelOnDrop notifies a target that a request was cancelled, unless the request completed first.
+struct CancelOnDrop {
+	peer: Peer<RoleClient>,
+	id: Option<RequestId>,
+	reason: &'static str,
+}
+
+impl Drop for CancelOnDrop {
+	fn drop(&mut self) {
+		let Some(id) = self.id.take() else {
+			return;
+		};
+		let peer = self.peer.clone();
+		let reason = self.reason.to_string();
+		tokio::spawn(async move {
+			let notification = ClientNotification::CancelledNotification(Notification {
+				method: Default::default(),
+				params: CancelledNotificationParam {
+					request_id: id,
+					reason: Some(reason),
+				},
+			});
+			if let Err(e) = peer.send_notification(notification).await {
+				tracing::debug!("failed to cancel request on target: {}", e);
+			}
+		});
+	}
+}
+
 #[derive(Debug)]
 struct OpenAPIHandler {
 	host: String,
@@ -711,7 +2254,7 @@ impl OpenAPIHandler {
 		let body = self
 			.client
 			.request(info.method.clone(), format!("{}{}", self.host, &info.path))
-			.json(args.as_ref().unwrap())
+			.json(&args.unwrap_or_default())
 			.send()
 			.await?
 			.text()
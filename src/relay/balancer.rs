@@ -0,0 +1,241 @@
+use crate::outbound::{Endpoint, LoadBalancing};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const EJECTION_TIME: Duration = Duration::from_secs(30);
+
+/// Address is a single endpoint of a target.
+/// With DNS discovery, the ip is the resolved address the host is pinned to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Address {
+	pub host: String,
+	pub port: u32,
+	pub ip: Option<IpAddr>,
+}
+
+impl fmt::Display for Address {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.ip {
+			Some(ip) => write!(f, "{}:{} ({})", self.host, self.port, ip),
+			None => write!(f, "{}:{}", self.host, self.port),
+		}
+	}
+}
+
+/// Resolves the endpoints of a target into addresses.
+/// With DNS discovery, every address returned for a host is a separate endpoint.
+pub async fn resolve(endpoints: &[Endpoint], dns_discovery: bool) -> std::io::Result<Vec<Address>> {
+	let mut addresses = Vec::new();
+	for endpoint in endpoints {
+		if !dns_discovery {
+			addresses.push(Address {
+				host: endpoint.host.clone(),
+				port: endpoint.port,
+				ip: None,
+			});
+			continue;
+		}
+		let port = u16::try_from(endpoint.port)
+			.map_err(|_| std::io::Error::other(format!("invalid port: {}", endpoint.port)))?;
+		let mut seen = HashSet::new();
+		for addr in tokio::net::lookup_host((endpoint.host.as_str(), port)).await? {
+			if seen.insert(addr.ip()) {
+				addresses.push(Address {
+					host: endpoint.host.clone(),
+					port: endpoint.port,
+					ip: Some(addr.ip()),
+				});
+			}
+		}
+	}
+	Ok(addresses)
+}
+
+/// EndpointHandle is the endpoint a connection was balanced to.
+#[derive(Clone, Debug)]
+pub struct EndpointHandle {
+	pub address: Address,
+	requests: Arc<AtomicUsize>,
+	connections: Arc<AtomicUsize>,
+}
+
+impl EndpointHandle {
+	fn new(address: Address) -> Self {
+		Self {
+			address,
+			requests: Arc::new(AtomicUsize::new(0)),
+			connections: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+
+	/// Counts a request as in flight until the returned guard is dropped.
+	pub fn start_request(&self) -> CountGuard {
+		CountGuard::new(&self.requests)
+	}
+
+	/// Counts a connection as open until the returned guard is dropped.
+	pub fn open_connection(&self) -> CountGuard {
+		CountGuard::new(&self.connections)
+	}
+}
+
+/// CountGuard decrements a counter of an endpoint when dropped.
+#[derive(Debug)]
+pub struct CountGuard(Arc<AtomicUsize>);
+
+impl CountGuard {
+	fn new(counter: &Arc<AtomicUsize>) -> Self {
+		counter.fetch_add(1, Ordering::Relaxed);
+		Self(counter.clone())
+	}
+}
+
+impl Drop for CountGuard {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointStatus {
+	pub address: String,
+	pub requests: usize,
+	pub connections: usize,
+	pub ejected: bool,
+	pub ejections: u64,
+}
+
+#[derive(Clone, Debug)]
+struct EndpointState {
+	handle: EndpointHandle,
+	ejected_until: Option<Instant>,
+	ejections: u64,
+}
+
+impl EndpointState {
+	fn ejected(&self, now: Instant) -> bool {
+		self.ejected_until.is_some_and(|until| now < until)
+	}
+}
+
+/// Balancer spreads the connections to a target across its endpoints.
+#[derive(Clone, Debug, Default)]
+pub struct Balancer {
+	endpoints: Vec<EndpointState>,
+	next: usize,
+}
+
+impl Balancer {
+	/// Replaces the endpoints, keeping the state of those that are still present.
+	pub fn update(&mut self, addresses: Vec<Address>) {
+		let mut existing = std::mem::take(&mut self.endpoints);
+		self.endpoints = addresses
+			.into_iter()
+			.map(
+				|address| match existing.iter().position(|e| e.handle.address == address) {
+					Some(i) => existing.swap_remove(i),
+					None => EndpointState {
+						handle: EndpointHandle::new(address),
+						ejected_until: None,
+						ejections: 0,
+					},
+				},
+			)
+			.collect();
+	}
+
+	/// Picks the endpoint for a new connection.
+	/// The affinity endpoint is kept while it is healthy, so a session reconnects to the same replica.
+	/// Ejected endpoints are skipped, unless every endpoint is ejected.
+	pub fn pick(
+		&mut self,
+		strategy: &LoadBalancing,
+		affinity: Option<&Address>,
+	) -> Option<EndpointHandle> {
+		let now = Instant::now();
+		let mut candidates: Vec<&EndpointState> =
+			self.endpoints.iter().filter(|e| !e.ejected(now)).collect();
+		if candidates.is_empty() {
+			candidates = self.endpoints.iter().collect();
+		}
+		if let Some(affinity) = affinity {
+			if let Some(endpoint) = candidates.iter().find(|e| &e.handle.address == affinity) {
+				return Some(endpoint.handle.clone());
+			}
+		}
+		let picked = match strategy {
+			LoadBalancing::RoundRobin => {
+				let endpoint = candidates.get(self.next % candidates.len().max(1)).copied();
+				self.next = self.next.wrapping_add(1);
+				endpoint
+			},
+			LoadBalancing::LeastConnections => candidates
+				.into_iter()
+				.min_by_key(|e| e.handle.connections.load(Ordering::Relaxed)),
+		};
+		picked.map(|e| e.handle.clone())
+	}
+
+	/// Takes an endpoint out of rotation after a failed connection.
+	pub fn eject(&mut self, address: &Address) {
+		if let Some(endpoint) = self
+			.endpoints
+			.iter_mut()
+			.find(|e| &e.handle.address == address)
+		{
+			endpoint.ejected_until = Some(Instant::now() + EJECTION_TIME);
+			endpoint.ejections += 1;
+		}
+	}
+
+	pub fn status(&self) -> Vec<EndpointStatus> {
+		let now = Instant::now();
+		self
+			.endpoints
+			.iter()
+			.map(|e| EndpointStatus {
+				address: e.handle.address.to_string(),
+				requests: e.handle.requests.load(Ordering::Relaxed),
+				connections: e.handle.connections.load(Ordering::Relaxed),
+				ejected: e.ejected(now),
+				ejections: e.ejections,
+			})
+			.collect()
+	}
+}
+
+#[test]
+fn test_pick() {
+	let address = |host: &str| Address {
+		host: host.to_string(),
+		port: 80,
+		ip: None,
+	};
+	let mut balancer = Balancer::default();
+	balancer.update(vec![address("a"), address("b")]);
+	let first = balancer.pick(&LoadBalancing::RoundRobin, None).unwrap();
+	let second = balancer.pick(&LoadBalancing::RoundRobin, None).unwrap();
+	assert_ne!(first.address, second.address);
+	let affine = balancer.pick(&LoadBalancing::RoundRobin, Some(&first.address));
+	assert_eq!(affine.unwrap().address, first.address);
+
+	// Least connections picks the endpoint with the fewest open connections
+	let _connection = first.open_connection();
+	let least = balancer.pick(&LoadBalancing::LeastConnections, None);
+	assert_eq!(least.unwrap().address, second.address);
+
+	// Ejected endpoints are skipped, even with affinity, unless every endpoint is ejected
+	balancer.eject(&second.address);
+	let least = balancer.pick(&LoadBalancing::LeastConnections, None);
+	assert_eq!(least.unwrap().address, first.address);
+	let affine = balancer.pick(&LoadBalancing::RoundRobin, Some(&second.address));
+	assert_eq!(affine.unwrap().address, first.address);
+	balancer.eject(&first.address);
+	assert!(balancer.pick(&LoadBalancing::RoundRobin, None).is_some());
+}
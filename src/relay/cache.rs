@@ -0,0 +1,305 @@
+use crate::outbound::ResponseCache;
+use crate::rbac::Identity;
+use itertools::Itertools;
+use rmcp::model::{CallToolResult, JsonObject};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A page of a list, and the cursor of the next page.
+type Page<T> = (Vec<T>, Option<String>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ListKey {
+	target: String,
+	resource_type: String,
+	cursor: Option<String>,
+}
+
+struct ListEntry {
+	page: Arc<dyn Any + Send + Sync>,
+	generation: u64,
+	expires_at: Instant,
+}
+
+/// ListCache keeps the list pages each target returned to a session, so repeated lists are served locally.
+/// Pages are dropped once their TTL expires, when the target notifies that the list changed,
+/// or when the config of the target changed generation.
+#[derive(Clone, Default)]
+pub(super) struct ListCache(Arc<Mutex<HashMap<ListKey, ListEntry>>>);
+
+impl ListCache {
+	pub(super) fn get<T: Clone + 'static>(
+		&self,
+		target: &str,
+		resource_type: &str,
+		cursor: Option<&str>,
+		generation: u64,
+	) -> Option<Page<T>> {
+		let key = ListKey {
+			target: target.to_string(),
+			resource_type: resource_type.to_string(),
+			cursor: cursor.map(str::to_string),
+		};
+		let mut entries = self.0.lock().unwrap();
+		let entry = entries.get(&key)?;
+		if entry.generation != generation || entry.expires_at <= Instant::now() {
+			entries.remove(&key);
+			return None;
+		}
+		entry.page.downcast_ref::<Page<T>>().cloned()
+	}
+
+	pub(super) fn insert<T: Send + Sync + 'static>(
+		&self,
+		target: &str,
+		resource_type: &str,
+		cursor: Option<&str>,
+		generation: u64,
+		ttl: Duration,
+		page: Page<T>,
+	) {
+		let key = ListKey {
+			target: target.to_string(),
+			resource_type: resource_type.to_string(),
+			cursor: cursor.map(str::to_string),
+		};
+		let entry = ListEntry {
+			page: Arc::new(page),
+			generation,
+			expires_at: Instant::now() + ttl,
+		};
+		self.0.lock().unwrap().insert(key, entry);
+	}
+
+	/// Drops the cached pages of the given resource types of a target.
+	pub(super) fn invalidate(&self, target: &str, resource_types: &[&str]) {
+		self.0.lock().unwrap().retain(|key, _| {
+			key.target != target || !resource_types.contains(&key.resource_type.as_str())
+		});
+	}
+
+	/// Drops every cached page of a target.
+	pub(super) fn invalidate_target(&self, target: &str) {
+		self.0.lock().unwrap().retain(|key, _| key.target != target);
+	}
+}
+
+/// The cached results of a tool, by call.
+type ToolResults = HashMap<ToolCall, ToolEntry>;
+
+struct ToolEntry {
+	result: CallToolResult,
+	generation: u64,
+	inserted_at: Instant,
+	expires_at: Instant,
+}
+
+/// ToolCache keeps the results of the tools configured to be cached, shared by every session.
+/// Results are grouped by tool, so evicting the results of a tool never scans those of other tools.
+#[derive(Clone, Default)]
+pub struct ToolCache(Arc<Mutex<HashMap<(String, String), ToolResults>>>);
+
+impl ToolCache {
+	pub(super) fn get(&self, call: &ToolCall, generation: u64) -> Option<CallToolResult> {
+		let mut tools = self.0.lock().unwrap();
+		let entries = tools.get_mut(&call.tool_key())?;
+		let entry = entries.get(call)?;
+		if entry.generation != generation || entry.expires_at <= Instant::now() {
+			entries.remove(call);
+			return None;
+		}
+		Some(entry.result.clone())
+	}
+
+	/// Caches a result, dropping the oldest results of the tool beyond its maximum number of entries.
+	pub(super) fn insert(
+		&self,
+		call: &ToolCall,
+		generation: u64,
+		config: &ResponseCache,
+		result: CallToolResult,
+	) {
+		let now = Instant::now();
+		let mut tools = self.0.lock().unwrap();
+		let entries = tools.entry(call.tool_key()).or_default();
+		// A result replacing one for the same call does not count against the maximum
+		entries.remove(call);
+		entries.retain(|_, e| e.expires_at > now);
+		if entries.len() >= config.max_entries {
+			let mut cached: Vec<_> = entries
+				.iter()
+				.map(|(k, e)| (e.inserted_at, k.clone()))
+				.collect();
+			cached.sort_by_key(|(inserted_at, _)| *inserted_at);
+			let excess = cached.len() + 1 - config.max_entries.max(1);
+			for (_, key) in cached.into_iter().take(excess) {
+				entries.remove(&key);
+			}
+		}
+		entries.insert(
+			call.clone(),
+			ToolEntry {
+				result,
+				generation,
+				inserted_at: now,
+				expires_at: now + config.ttl,
+			},
+		);
+	}
+
+	/// Drops every cached result of a target.
+	pub fn remove(&self, target: &str) {
+		self.0.lock().unwrap().retain(|(t, _), _| t != target);
+	}
+}
+
+/// ToolCall identifies a tool call for caching: the tool, its arguments and, optionally, the caller.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) struct ToolCall {
+	target: String,
+	tool: String,
+	arguments: String,
+	identity: Option<String>,
+}
+
+impl ToolCall {
+	pub(super) fn new(
+		target: &str,
+		tool: &str,
+		arguments: Option<&JsonObject>,
+		identity: Option<&Identity>,
+	) -> Self {
+		let arguments = arguments.cloned().map(Value::Object).unwrap_or_default();
+		Self {
+			target: target.to_string(),
+			tool: tool.to_string(),
+			arguments: normalize(&arguments),
+			identity: identity.map(|id| normalize(&serde_json::to_value(id).unwrap_or_default())),
+		}
+	}
+
+	fn tool_key(&self) -> (String, String) {
+		(self.target.clone(), self.tool.clone())
+	}
+}
+
+/// Serializes a JSON value with the keys of every object sorted, so equal arguments have the same key.
+fn normalize(value: &Value) -> String {
+	match value {
+		Value::Object(map) => {
+			let fields = map
+				.iter()
+				.sorted_by(|(a, _), (b, _)| a.cmp(b))
+				.map(|(k, v)| format!("{}:{}", Value::String(k.clone()), normalize(v)))
+				.join(",");
+			format!("{{{}}}", fields)
+		},
+		Value::Array(items) => format!("[{}]", items.iter().map(normalize).join(",")),
+		value => value.to_string(),
+	}
+}
+
+#[test]
+fn test_tool_cache() {
+	let config = ResponseCache {
+		ttl: Duration::from_secs(60),
+		max_entries: 2,
+		per_identity: false,
+	};
+	let result = |text: &str| CallToolResult {
+		content: vec![rmcp::model::Content::text(text)],
+		is_error: None,
+	};
+	let arguments = |value: Value| value.as_object().cloned();
+
+	// Arguments are equal whatever the order of their keys
+	let call = ToolCall::new(
+		"t",
+		"echo",
+		arguments(serde_json::json!({"x": 1, "y": {"b": 2, "a": 1}})).as_ref(),
+		None,
+	);
+	let reordered = ToolCall::new(
+		"t",
+		"echo",
+		arguments(serde_json::json!({"y": {"a": 1, "b": 2}, "x": 1})).as_ref(),
+		None,
+	);
+	assert_eq!(call, reordered);
+
+	let cache = ToolCache::default();
+	cache.insert(&call, 1, &config, result("1"));
+	assert_eq!(cache.get(&reordered, 1), Some(result("1")));
+	assert_eq!(cache.get(&reordered, 2), None);
+
+	// The oldest results of a tool are dropped beyond its maximum number of entries
+	let calls: Vec<ToolCall> = (0..3)
+		.map(|i| {
+			ToolCall::new(
+				"t",
+				"echo",
+				arguments(serde_json::json!({ "i": i })).as_ref(),
+				None,
+			)
+		})
+		.collect();
+	for call in &calls {
+		cache.insert(call, 1, &config, result("r"));
+		std::thread::sleep(Duration::from_millis(1));
+	}
+	assert_eq!(cache.get(&calls[0], 1), None);
+	assert!(cache.get(&calls[1], 1).is_some());
+	assert!(cache.get(&calls[2], 1).is_some());
+
+	// Caching a call again replaces its result, without evicting the results of other calls
+	cache.insert(&calls[2], 1, &config, result("again"));
+	assert!(cache.get(&calls[1], 1).is_some());
+	assert_eq!(cache.get(&calls[2], 1), Some(result("again")));
+
+	// Other tools are not evicted
+	let other = ToolCall::new("t", "other", None, None);
+	cache.insert(&other, 1, &config, result("o"));
+	assert!(cache.get(&calls[1], 1).is_some());
+	cache.remove("t");
+	assert_eq!(cache.get(&other, 1), None);
+}
+
+#[test]
+fn test_list_cache() {
+	let cache = ListCache::default();
+	let page =
+		|items: &[&str]| -> Page<String> { (items.iter().map(|i| i.to_string()).collect(), None) };
+	let ttl = Duration::from_secs(60);
+	cache.insert("a", "tool", None, 1, ttl, page(&["echo"]));
+	cache.insert("a", "tool", Some("next"), 1, ttl, page(&["add"]));
+	cache.insert("a", "prompt", None, 1, ttl, page(&["greet"]));
+	cache.insert("b", "tool", None, 1, ttl, page(&["search"]));
+
+	assert_eq!(cache.get("a", "tool", None, 1), Some(page(&["echo"])));
+	assert_eq!(
+		cache.get("a", "tool", Some("next"), 1),
+		Some(page(&["add"]))
+	);
+	assert_eq!(cache.get::<String>("a", "resource", None, 1), None);
+	// Pages are not returned as another type
+	assert_eq!(cache.get::<u32>("a", "tool", None, 1), None);
+
+	// A page cached for another generation of the target is dropped
+	assert_eq!(cache.get::<String>("a", "tool", None, 2), None);
+	assert_eq!(cache.get::<String>("a", "tool", None, 1), None);
+
+	// A list change only drops the pages of that list
+	cache.invalidate("a", &["tool"]);
+	assert_eq!(cache.get::<String>("a", "tool", Some("next"), 1), None);
+	assert_eq!(cache.get("a", "prompt", None, 1), Some(page(&["greet"])));
+	cache.invalidate_target("a");
+	assert_eq!(cache.get::<String>("a", "prompt", None, 1), None);
+	assert_eq!(cache.get("b", "tool", None, 1), Some(page(&["search"])));
+
+	// Expired pages are dropped
+	cache.insert("c", "tool", None, 1, Duration::ZERO, page(&["echo"]));
+	assert_eq!(cache.get::<String>("c", "tool", None, 1), None);
+}
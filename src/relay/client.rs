@@ -0,0 +1,308 @@
+use super::cache::ListCache;
+use crate::rbac;
+use crate::xds::XdsStore;
+use rmcp::model::{
+	ClientCapabilities, ClientInfo, CreateMessageRequestParam, CreateMessageResult, ErrorData,
+	Implementation, ListRootsResult, LoggingLevel, LoggingMessageNotificationParam,
+	ResourceUpdatedNotificationParam,
+};
+use rmcp::service::{Peer, RequestContext};
+use rmcp::{ClientHandler, RoleClient, RoleServer};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Downstream is the client session a relay serves.
+/// Connections to targets are made per session, so anything a target sends belongs to this session.
+#[derive(Clone)]
+pub(super) struct Downstream {
+	peer: Arc<RwLock<Option<Peer<RoleServer>>>>,
+	state: Arc<RwLock<XdsStore>>,
+	id: rbac::Identity,
+	// The resources the client subscribed to, by target and upstream URI, with the URI the client used
+	subscriptions: Arc<Mutex<HashMap<(String, String), String>>>,
+	// The log level the client set, which is also set on every target it uses
+	level: Arc<RwLock<Option<LoggingLevel>>>,
+	lists: ListCache,
+}
+
+impl Downstream {
+	pub(super) fn new(state: Arc<RwLock<XdsStore>>, id: rbac::Identity) -> Self {
+		Self {
+			peer: Arc::new(RwLock::new(None)),
+			state,
+			id,
+			subscriptions: Arc::new(Mutex::new(HashMap::new())),
+			level: Arc::new(RwLock::new(None)),
+			lists: ListCache::default(),
+		}
+	}
+
+	pub(super) fn set(&self, peer: Peer<RoleServer>) {
+		*self.peer.write().unwrap() = Some(peer);
+	}
+
+	pub(super) fn get(&self) -> Option<Peer<RoleServer>> {
+		self.peer.read().unwrap().clone()
+	}
+
+	pub(super) fn lists(&self) -> &ListCache {
+		&self.lists
+	}
+
+	pub(super) fn set_level(&self, level: LoggingLevel) {
+		*self.level.write().unwrap() = Some(level);
+	}
+
+	pub(super) fn level(&self) -> Option<LoggingLevel> {
+		self.level.read().unwrap().clone()
+	}
+
+	/// Returns whether a log message of a target is at least as severe as the client
+	/// and the target configuration ask for.
+	fn logs(&self, target: &str, level: &LoggingLevel) -> bool {
+		let min_level = self
+			.state
+			.read()
+			.unwrap()
+			.targets
+			.get(target)
+			.and_then(|t| t.min_log_level.clone());
+		[min_level, self.level()]
+			.iter()
+			.flatten()
+			.all(|min| severity(level) >= severity(min))
+	}
+
+	/// Records a subscription, returning whether the client was not subscribed yet.
+	pub(super) fn subscribe(&self, target: &str, uri: &str, client_uri: &str) -> bool {
+		let mut subscriptions = self.subscriptions.lock().unwrap();
+		let key = (target.to_string(), uri.to_string());
+		if subscriptions.contains_key(&key) {
+			return false;
+		}
+		subscriptions.insert(key, client_uri.to_string());
+		true
+	}
+
+	/// Removes a subscription, returning whether the client was subscribed.
+	pub(super) fn unsubscribe(&self, target: &str, uri: &str) -> bool {
+		self
+			.subscriptions
+			.lock()
+			.unwrap()
+			.remove(&(target.to_string(), uri.to_string()))
+			.is_some()
+	}
+
+	/// Removes every subscription, returning their target and upstream URI.
+	pub(super) fn clear_subscriptions(&self) -> Vec<(String, String)> {
+		let mut subscriptions = self.subscriptions.lock().unwrap();
+		subscriptions.drain().map(|(key, _)| key).collect()
+	}
+
+	fn subscription(&self, target: &str, uri: &str) -> Option<String> {
+		self
+			.subscriptions
+			.lock()
+			.unwrap()
+			.get(&(target.to_string(), uri.to_string()))
+			.cloned()
+	}
+
+	fn allows(&self, resource: &rbac::ResourceType) -> bool {
+		self
+			.state
+			.read()
+			.unwrap()
+			.policies
+			.validate(resource, &self.id)
+	}
+}
+
+/// UpstreamClient is the client handler of a connection to a target.
+/// It relays the requests and notifications of the target to the downstream session.
+#[derive(Clone)]
+pub(super) struct UpstreamClient {
+	target: String,
+	downstream: Downstream,
+	peer: Option<Peer<RoleClient>>,
+}
+
+impl std::fmt::Debug for UpstreamClient {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("UpstreamClient")
+			.field("target", &self.target)
+			.finish()
+	}
+}
+
+impl UpstreamClient {
+	pub(super) fn new(target: &str, downstream: Downstream) -> Self {
+		Self {
+			target: target.to_string(),
+			downstream,
+			peer: None,
+		}
+	}
+
+	fn downstream(&self) -> Result<Peer<RoleServer>, ErrorData> {
+		self
+			.downstream
+			.get()
+			.ok_or_else(|| ErrorData::internal_error("no client session to relay to", None))
+	}
+}
+
+impl ClientHandler for UpstreamClient {
+	async fn create_message(
+		&self,
+		params: CreateMessageRequestParam,
+		_context: RequestContext<RoleClient>,
+	) -> Result<CreateMessageResult, ErrorData> {
+		if !self.downstream.allows(&rbac::ResourceType::Sampling {
+			id: self.target.clone(),
+		}) {
+			return Err(ErrorData::invalid_request(
+				format!("target {} is not allowed to request sampling", self.target),
+				None,
+			));
+		}
+		self
+			.downstream()?
+			.create_message(params)
+			.await
+			.map_err(|e| relay_error(&self.target, e))
+	}
+
+	async fn list_roots(
+		&self,
+		_context: RequestContext<RoleClient>,
+	) -> Result<ListRootsResult, ErrorData> {
+		self
+			.downstream()?
+			.list_roots()
+			.await
+			.map_err(|e| relay_error(&self.target, e))
+	}
+
+	// Elicitation is not relayed: rmcp has no elicitation request at this revision, so targets cannot send one
+	// and clients cannot answer one.
+
+	// Progress is not relayed: rmcp drops the `_meta` of requests, so the progress token of the client
+	// can neither be read nor sent to the target, and the tokens of a target mean nothing to the client.
+
+	async fn on_resource_updated(&self, params: ResourceUpdatedNotificationParam) {
+		// Updates for resources the client did not subscribe to, or has unsubscribed from, are dropped
+		let Some(uri) = self.downstream.subscription(&self.target, &params.uri) else {
+			return;
+		};
+		let Some(peer) = self.downstream.get() else {
+			return;
+		};
+		if let Err(e) = peer
+			.notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+			.await
+		{
+			tracing::debug!(
+				"failed to relay resource update from target {}: {}",
+				self.target,
+				e
+			);
+		}
+	}
+
+	async fn on_logging_message(&self, params: LoggingMessageNotificationParam) {
+		if !self.downstream.logs(&self.target, &params.level) {
+			return;
+		}
+		let Some(peer) = self.downstream.get() else {
+			return;
+		};
+		// The logger names the target, so the client can tell apart the messages of each target
+		let logger = match params.logger {
+			Some(logger) => format!("{}/{}", self.target, logger),
+			None => self.target.clone(),
+		};
+		let params = LoggingMessageNotificationParam {
+			level: params.level,
+			logger: Some(logger),
+			data: params.data,
+		};
+		if let Err(e) = peer.notify_logging_message(params).await {
+			tracing::debug!(
+				"failed to relay log message from target {}: {}",
+				self.target,
+				e
+			);
+		}
+	}
+
+	async fn on_tool_list_changed(&self) {
+		self.downstream.lists.invalidate(&self.target, &["tool"]);
+	}
+
+	async fn on_prompt_list_changed(&self) {
+		self.downstream.lists.invalidate(&self.target, &["prompt"]);
+	}
+
+	async fn on_resource_list_changed(&self) {
+		self
+			.downstream
+			.lists
+			.invalidate(&self.target, &["resource", "resource_template"]);
+	}
+
+	fn get_peer(&self) -> Option<Peer<RoleClient>> {
+		self.peer.clone()
+	}
+
+	fn set_peer(&mut self, peer: Peer<RoleClient>) {
+		self.peer = Some(peer);
+	}
+
+	/// Advertises roots and sampling only if the client supports them, as they are relayed to it.
+	/// Targets are connected to after the client initialized, so its capabilities are known.
+	fn get_info(&self) -> ClientInfo {
+		let client = self
+			.downstream
+			.get()
+			.map(|peer| peer.peer_info().capabilities.clone())
+			.unwrap_or_default();
+		ClientInfo {
+			protocol_version: Default::default(),
+			capabilities: ClientCapabilities {
+				experimental: None,
+				roots: client.roots,
+				sampling: client.sampling,
+			},
+			client_info: Implementation::from_build_env(),
+		}
+	}
+}
+
+fn severity(level: &LoggingLevel) -> u8 {
+	match level {
+		LoggingLevel::Debug => 0,
+		LoggingLevel::Info => 1,
+		LoggingLevel::Notice => 2,
+		LoggingLevel::Warning => 3,
+		LoggingLevel::Error => 4,
+		LoggingLevel::Critical => 5,
+		LoggingLevel::Alert => 6,
+		LoggingLevel::Emergency => 7,
+	}
+}
+
+/// Maps the failure of a request relayed to the client, keeping the error the client returned.
+fn relay_error(target: &str, e: rmcp::ServiceError) -> ErrorData {
+	match e {
+		rmcp::ServiceError::McpError(e) => e,
+		e => {
+			tracing::debug!("failed to relay request from target {}: {}", target, e);
+			ErrorData::internal_error(
+				format!("failed to relay request to the client: {}", e),
+				None,
+			)
+		},
+	}
+}
@@ -0,0 +1,141 @@
+use super::transform;
+use crate::outbound::CompositeTool;
+use rmcp::model::{CallToolResult, Content, JsonObject, RawContent, Tool};
+use serde_json::{Value, json};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Returns the tools of a composite target, as they are listed.
+pub(super) fn list_tools(tools: &[CompositeTool]) -> Vec<Tool> {
+	tools
+		.iter()
+		.map(|tool| Tool {
+			name: Cow::Owned(tool.name.clone()),
+			description: tool.description.clone().map(Cow::Owned),
+			input_schema: Arc::new(tool.input_schema.clone()),
+		})
+		.collect()
+}
+
+/// Scope holds what the templates of a composite tool can reference:
+/// the arguments of the tool, and the result of each step that ran.
+pub(super) struct Scope(Value);
+
+impl Scope {
+	pub(super) fn new(arguments: Option<JsonObject>) -> Self {
+		Self(json!({
+			"arguments": arguments.unwrap_or_default(),
+			"steps": {},
+		}))
+	}
+
+	pub(super) fn insert_step(&mut self, id: &str, result: &CallToolResult) {
+		if let Some(Value::Object(steps)) = self.0.get_mut("steps") {
+			steps.insert(id.to_string(), step_result(result));
+		}
+	}
+
+	/// Renders every string of a value that references the scope.
+	pub(super) fn render(&self, value: &Value) -> Result<Value, String> {
+		match value {
+			Value::Object(object) => Ok(Value::Object(
+				object
+					.iter()
+					.map(|(k, v)| Ok((k.clone(), self.render(v)?)))
+					.collect::<Result<_, String>>()?,
+			)),
+			Value::Array(items) => Ok(Value::Array(
+				items
+					.iter()
+					.map(|v| self.render(v))
+					.collect::<Result<_, String>>()?,
+			)),
+			value => transform::render_template(value, &|reference| self.lookup(reference)),
+		}
+	}
+
+	/// Looks up a dotted path, such as `steps.create.json.id`.
+	fn lookup(&self, reference: &str) -> Result<Value, String> {
+		reference
+			.split('.')
+			.try_fold(&self.0, |value, key| match value {
+				Value::Object(object) => object.get(key),
+				Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+				_ => None,
+			})
+			.cloned()
+			.ok_or_else(|| format!("unknown reference {}", reference))
+	}
+}
+
+/// The value a step result is referenced as: its text, and its text parsed as JSON if it is JSON.
+fn step_result(result: &CallToolResult) -> Value {
+	let text = result
+		.content
+		.iter()
+		.filter_map(|content| match &content.raw {
+			RawContent::Text(text) => Some(text.text.as_str()),
+			_ => None,
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+	let parsed = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+	json!({
+		"text": text,
+		"json": parsed,
+		"isError": result.is_error.unwrap_or_default(),
+	})
+}
+
+/// Builds the result of a composite tool from its output template.
+pub(super) fn output(scope: &Scope, template: &Value) -> Result<CallToolResult, String> {
+	let text = match scope.render(template)? {
+		Value::String(text) => text,
+		value => value.to_string(),
+	};
+	Ok(CallToolResult {
+		content: vec![Content::text(text)],
+		is_error: None,
+	})
+}
+
+#[test]
+fn test_scope() {
+	let mut scope = Scope::new(json!({ "city": "Paris" }).as_object().cloned());
+	scope.insert_step(
+		"lookup",
+		&CallToolResult {
+			content: vec![Content::text(r#"{"id": 7, "tags": ["a", "b"]}"#)],
+			is_error: None,
+		},
+	);
+	scope.insert_step(
+		"note",
+		&CallToolResult {
+			content: vec![Content::text("not json")],
+			is_error: Some(true),
+		},
+	);
+
+	assert_eq!(scope.lookup("arguments.city"), Ok(json!("Paris")));
+	assert_eq!(scope.lookup("steps.lookup.json.id"), Ok(json!(7)));
+	assert_eq!(scope.lookup("steps.lookup.json.tags.1"), Ok(json!("b")));
+	assert_eq!(scope.lookup("steps.note.json"), Ok(Value::Null));
+	assert_eq!(scope.lookup("steps.note.isError"), Ok(json!(true)));
+	assert!(scope.lookup("steps.missing.text").is_err());
+
+	// A string that is a single reference keeps the type of the value
+	assert_eq!(
+		scope.render(&json!({
+			"id": "{{ steps.lookup.json.id }}",
+			"query": ["weather in {{arguments.city}}"],
+		})),
+		Ok(json!({ "id": 7, "query": ["weather in Paris"] }))
+	);
+	assert!(scope.render(&json!("{{arguments.city")).is_err());
+
+	let result = output(&scope, &json!({ "id": "{{steps.lookup.json.id}}" })).unwrap();
+	assert_eq!(result.content, vec![Content::text(r#"{"id":7}"#)]);
+	let result = output(&scope, &json!("{{steps.note.text}}")).unwrap();
+	assert_eq!(result.content, vec![Content::text("not json")]);
+}
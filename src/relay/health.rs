@@ -0,0 +1,339 @@
+use crate::outbound::CircuitBreaker;
+use crate::relay::balancer::{Balancer, EndpointStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// How long a connection must stay up before the backoff is reset, so targets that fail right after
+// connecting are not reconnected to on every request
+const STABLE_AFTER: Duration = Duration::from_secs(10);
+const STDERR_LINES: usize = 500;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpstreamState {
+	Connecting,
+	Ready,
+	Failed,
+}
+
+impl UpstreamState {
+	pub const ALL: [UpstreamState; 3] = [
+		UpstreamState::Connecting,
+		UpstreamState::Ready,
+		UpstreamState::Failed,
+	];
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			UpstreamState::Connecting => "connecting",
+			UpstreamState::Ready => "ready",
+			UpstreamState::Failed => "failed",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BreakerState {
+	Closed,
+	Open,
+	HalfOpen,
+}
+
+impl BreakerState {
+	pub const ALL: [BreakerState; 3] = [
+		BreakerState::Closed,
+		BreakerState::Open,
+		BreakerState::HalfOpen,
+	];
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			BreakerState::Closed => "closed",
+			BreakerState::Open => "open",
+			BreakerState::HalfOpen => "half_open",
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamStatus {
+	pub state: UpstreamState,
+	pub restarts: u64,
+	pub last_error: Option<String>,
+	pub breaker: BreakerState,
+	#[serde(skip)]
+	backoff: Duration,
+	#[serde(skip)]
+	retry_at: Option<Instant>,
+	#[serde(skip)]
+	failures: u32,
+	#[serde(skip)]
+	probe_at: Option<Instant>,
+	#[serde(skip)]
+	ready_at: Option<Instant>,
+}
+
+impl Default for UpstreamStatus {
+	fn default() -> Self {
+		Self {
+			state: UpstreamState::Connecting,
+			restarts: 0,
+			last_error: None,
+			breaker: BreakerState::Closed,
+			backoff: INITIAL_BACKOFF,
+			retry_at: None,
+			failures: 0,
+			probe_at: None,
+			ready_at: None,
+		}
+	}
+}
+
+/// UpstreamStore tracks the health of the connections to each target.
+/// Connections are owned by each session's pool, so this is the most recent state seen by any of them.
+#[derive(Clone, Default, Serialize)]
+pub struct UpstreamStore {
+	by_name: HashMap<String, UpstreamStatus>,
+	#[serde(skip)]
+	stderr: HashMap<String, Arc<LogBuffer>>,
+	#[serde(skip)]
+	balancers: HashMap<String, Balancer>,
+	#[serde(skip)]
+	instructions: BTreeMap<String, String>,
+}
+
+impl UpstreamStore {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn state(&self, name: &str) -> Option<UpstreamState> {
+		self.by_name.get(name).map(|s| s.state)
+	}
+
+	pub fn last_error(&self, name: &str) -> Option<String> {
+		self.by_name.get(name).and_then(|s| s.last_error.clone())
+	}
+
+	/// Returns false while a target is backing off after a failed connection attempt.
+	pub fn should_connect(&self, name: &str) -> bool {
+		match self.by_name.get(name).and_then(|s| s.retry_at) {
+			Some(retry_at) => Instant::now() >= retry_at,
+			None => true,
+		}
+	}
+
+	pub fn connecting(&mut self, name: &str) {
+		self.by_name.entry(name.to_string()).or_default().state = UpstreamState::Connecting;
+	}
+
+	/// Records a new connection. The backoff is only reset once the connection is lost after staying up for a while.
+	pub fn ready(&mut self, name: &str) {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		status.state = UpstreamState::Ready;
+		status.retry_at = None;
+		status.ready_at = Some(Instant::now());
+	}
+
+	/// Records a failed connection attempt, backing off exponentially before the next one.
+	pub fn failed(&mut self, name: &str, error: String) {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		status.state = UpstreamState::Failed;
+		status.last_error = Some(error);
+		status.retry_at = Some(Instant::now() + status.backoff);
+		status.backoff = std::cmp::min(MAX_BACKOFF, status.backoff * 2);
+	}
+
+	/// Records that an established connection was lost. The next connection attempt is a restart,
+	/// made after backing off unless the connection had been stable.
+	pub fn lost(&mut self, name: &str, error: String) {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		let now = Instant::now();
+		if status
+			.ready_at
+			.is_some_and(|ready_at| now.duration_since(ready_at) >= STABLE_AFTER)
+		{
+			status.backoff = INITIAL_BACKOFF;
+		}
+		status.state = UpstreamState::Failed;
+		status.restarts += 1;
+		status.last_error = Some(error);
+		status.ready_at = None;
+		status.retry_at = Some(now + status.backoff);
+		status.backoff = std::cmp::min(MAX_BACKOFF, status.backoff * 2);
+	}
+
+	pub fn breaker(&self, name: &str) -> Option<BreakerState> {
+		self.by_name.get(name).map(|s| s.breaker)
+	}
+
+	/// Returns whether a request may be sent to a target.
+	/// Once the open interval has passed, a single probe request is let through every interval
+	/// until one of them succeeds.
+	pub fn admit(&mut self, name: &str, breaker: &CircuitBreaker) -> bool {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		if status.breaker == BreakerState::Closed {
+			return true;
+		}
+		let now = Instant::now();
+		match status.probe_at {
+			Some(probe_at) if now < probe_at => false,
+			_ => {
+				status.breaker = BreakerState::HalfOpen;
+				status.probe_at = Some(now + breaker.open_interval);
+				true
+			},
+		}
+	}
+
+	pub fn request_succeeded(&mut self, name: &str) {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		status.breaker = BreakerState::Closed;
+		status.failures = 0;
+		status.probe_at = None;
+	}
+
+	/// Records a failed request, opening the circuit once there are too many in a row
+	/// or when a probe fails.
+	pub fn request_failed(&mut self, name: &str, breaker: &CircuitBreaker) {
+		let status = self.by_name.entry(name.to_string()).or_default();
+		status.failures += 1;
+		if status.breaker == BreakerState::HalfOpen || status.failures >= breaker.consecutive_failures {
+			status.breaker = BreakerState::Open;
+			status.probe_at = Some(Instant::now() + breaker.open_interval);
+		}
+	}
+
+	/// Returns the buffer holding the most recent stderr output of a target.
+	pub fn stderr(&mut self, name: &str) -> Arc<LogBuffer> {
+		self
+			.stderr
+			.entry(name.to_string())
+			.or_insert_with(|| Arc::new(LogBuffer::new(STDERR_LINES)))
+			.clone()
+	}
+
+	pub fn stderr_lines(&self, name: &str) -> Option<Vec<String>> {
+		self.stderr.get(name).map(|buffer| buffer.lines())
+	}
+
+	/// Returns the balancer spreading connections across the endpoints of a target.
+	pub fn balancer(&mut self, name: &str) -> &mut Balancer {
+		self.balancers.entry(name.to_string()).or_default()
+	}
+
+	pub fn endpoints(&self, name: &str) -> Option<Vec<EndpointStatus>> {
+		self.balancers.get(name).map(|balancer| balancer.status())
+	}
+
+	/// Records the instructions a target returned when it was last connected to.
+	pub fn set_instructions(&mut self, name: &str, instructions: Option<String>) {
+		match instructions.filter(|i| !i.trim().is_empty()) {
+			Some(instructions) => self.instructions.insert(name.to_string(), instructions),
+			None => self.instructions.remove(name),
+		};
+	}
+
+	/// Returns the instructions of each target, sorted by target name.
+	pub fn instructions(&self) -> &BTreeMap<String, String> {
+		&self.instructions
+	}
+
+	pub fn remove(&mut self, name: &str) {
+		self.by_name.remove(name);
+		self.stderr.remove(name);
+		self.balancers.remove(name);
+		self.instructions.remove(name);
+	}
+
+	pub fn clear(&mut self) {
+		self.by_name.clear();
+		self.stderr.clear();
+		self.balancers.clear();
+		self.instructions.clear();
+	}
+}
+
+/// LogBuffer keeps the last `capacity` lines written to it, with the pid of the process that wrote them.
+/// Each session spawns its own process, so the lines of several processes are interleaved.
+#[derive(Debug)]
+pub struct LogBuffer {
+	lines: Mutex<VecDeque<(Option<u32>, String)>>,
+	capacity: usize,
+}
+
+impl LogBuffer {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			lines: Mutex::new(VecDeque::with_capacity(capacity)),
+			capacity,
+		}
+	}
+
+	pub fn push(&self, pid: Option<u32>, line: String) {
+		let mut lines = self.lines.lock().unwrap();
+		if lines.len() == self.capacity {
+			lines.pop_front();
+		}
+		lines.push_back((pid, line));
+	}
+
+	/// Returns the lines, each prefixed with the pid of its process when it is known.
+	pub fn lines(&self) -> Vec<String> {
+		self
+			.lines
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(pid, line)| match pid {
+				Some(pid) => format!("[{}] {}", pid, line),
+				None => line.clone(),
+			})
+			.collect()
+	}
+}
+
+#[test]
+fn test_circuit_breaker() {
+	let breaker = CircuitBreaker {
+		consecutive_failures: 2,
+		open_interval: Duration::from_secs(60),
+	};
+	let mut upstreams = UpstreamStore::new();
+	assert!(upstreams.admit("a", &breaker));
+	upstreams.request_failed("a", &breaker);
+	assert_eq!(upstreams.breaker("a"), Some(BreakerState::Closed));
+	upstreams.request_failed("a", &breaker);
+	assert_eq!(upstreams.breaker("a"), Some(BreakerState::Open));
+	assert!(!upstreams.admit("a", &breaker));
+
+	// Without an open interval, the next request is a probe right away
+	let breaker = CircuitBreaker {
+		consecutive_failures: 1,
+		open_interval: Duration::ZERO,
+	};
+	upstreams.request_failed("b", &breaker);
+	assert_eq!(upstreams.breaker("b"), Some(BreakerState::Open));
+	assert!(upstreams.admit("b", &breaker));
+	assert_eq!(upstreams.breaker("b"), Some(BreakerState::HalfOpen));
+	upstreams.request_failed("b", &breaker);
+	assert_eq!(upstreams.breaker("b"), Some(BreakerState::Open));
+	assert!(upstreams.admit("b", &breaker));
+	upstreams.request_succeeded("b");
+	assert_eq!(upstreams.breaker("b"), Some(BreakerState::Closed));
+}
+
+#[test]
+fn test_log_buffer() {
+	let buffer = LogBuffer::new(2);
+	buffer.push(Some(1), "first".to_string());
+	buffer.push(Some(2), "second".to_string());
+	buffer.push(None, "third".to_string());
+	assert_eq!(buffer.lines(), vec!["[2] second", "third"]);
+}
@@ -1,6 +1,7 @@
 use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
 use prometheus_client::registry::Registry;
 
 use crate::metrics::Recorder;
@@ -9,8 +10,14 @@ pub struct Metrics {
 	tool_calls: Family<ToolCall, Counter>,
 	tool_call_errors: Family<ToolCallError, Counter>,
 	list_calls: Family<ListCall, Counter>,
+	list_errors: Family<ListError, Counter>,
 	read_resource_calls: Family<GetResourceCall, Counter>,
 	get_prompt_calls: Family<GetPromptCall, Counter>,
+	upstream_state: Family<UpstreamState, Gauge>,
+	upstream_restarts: Family<UpstreamRestart, Counter>,
+	circuit_breaker_state: Family<CircuitBreakerState, Gauge>,
+	tool_cache_hits: Family<ToolCacheHit, Counter>,
+	tool_cache_misses: Family<ToolCacheMiss, Counter>,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
@@ -30,6 +37,13 @@ pub struct ListCall {
 	pub resource_type: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ListError {
+	pub server: String,
+	pub resource_type: String,
+	pub error_type: String,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ToolCall {
 	pub server: String,
@@ -43,6 +57,35 @@ pub struct ToolCallError {
 	pub error_type: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct UpstreamState {
+	pub server: String,
+	pub state: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct UpstreamRestart {
+	pub server: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CircuitBreakerState {
+	pub server: String,
+	pub state: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ToolCacheHit {
+	pub server: String,
+	pub name: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ToolCacheMiss {
+	pub server: String,
+	pub name: String,
+}
+
 impl Metrics {
 	pub fn new(registry: &mut Registry) -> Self {
 		let tool_calls = Family::default();
@@ -66,6 +109,13 @@ impl Metrics {
 			list_calls.clone(),
 		);
 
+		let list_errors = Family::default();
+		registry.register(
+			"list_errors",
+			"The total number of targets left out of list responses because they failed",
+			list_errors.clone(),
+		);
+
 		let read_resource_calls = Family::default();
 		registry.register(
 			"read_resource_calls",
@@ -80,12 +130,53 @@ impl Metrics {
 			get_prompt_calls.clone(),
 		);
 
+		let upstream_state = Family::default();
+		registry.register(
+			"upstream_state",
+			"The connection state of each upstream target, set to 1 for the current state",
+			upstream_state.clone(),
+		);
+
+		let upstream_restarts = Family::default();
+		registry.register(
+			"upstream_restarts",
+			"The total number of times an upstream target connection was lost and restarted",
+			upstream_restarts.clone(),
+		);
+
+		let circuit_breaker_state = Family::default();
+		registry.register(
+			"circuit_breaker_state",
+			"The circuit breaker state of each upstream target, set to 1 for the current state",
+			circuit_breaker_state.clone(),
+		);
+
+		let tool_cache_hits = Family::default();
+		registry.register(
+			"tool_cache_hits",
+			"The total number of tool calls answered from the response cache",
+			tool_cache_hits.clone(),
+		);
+
+		let tool_cache_misses = Family::default();
+		registry.register(
+			"tool_cache_misses",
+			"The total number of cacheable tool calls that were not in the response cache",
+			tool_cache_misses.clone(),
+		);
+
 		Self {
 			tool_calls,
 			tool_call_errors,
 			list_calls,
+			list_errors,
 			read_resource_calls,
 			get_prompt_calls,
+			upstream_state,
+			upstream_restarts,
+			circuit_breaker_state,
+			tool_cache_hits,
+			tool_cache_misses,
 		}
 	}
 }
@@ -108,6 +199,12 @@ impl Recorder<ListCall, ()> for Metrics {
 	}
 }
 
+impl Recorder<ListError, ()> for Metrics {
+	fn record(&self, list_error: &ListError, _: ()) {
+		self.list_errors.get_or_create(list_error).inc();
+	}
+}
+
 impl Recorder<GetResourceCall, ()> for Metrics {
 	fn record(&self, get_resource_call: &GetResourceCall, _: ()) {
 		self
@@ -122,3 +219,57 @@ impl Recorder<GetPromptCall, ()> for Metrics {
 		self.get_prompt_calls.get_or_create(get_prompt_call).inc();
 	}
 }
+
+impl Recorder<UpstreamState, ()> for Metrics {
+	fn record(&self, upstream_state: &UpstreamState, _: ()) {
+		for state in crate::relay::health::UpstreamState::ALL {
+			let value = match state.as_str() == upstream_state.state {
+				true => 1,
+				false => 0,
+			};
+			self
+				.upstream_state
+				.get_or_create(&UpstreamState {
+					server: upstream_state.server.clone(),
+					state: state.as_str().to_string(),
+				})
+				.set(value);
+		}
+	}
+}
+
+impl Recorder<UpstreamRestart, ()> for Metrics {
+	fn record(&self, upstream_restart: &UpstreamRestart, _: ()) {
+		self.upstream_restarts.get_or_create(upstream_restart).inc();
+	}
+}
+
+impl Recorder<CircuitBreakerState, ()> for Metrics {
+	fn record(&self, breaker_state: &CircuitBreakerState, _: ()) {
+		for state in crate::relay::health::BreakerState::ALL {
+			let value = match state.as_str() == breaker_state.state {
+				true => 1,
+				false => 0,
+			};
+			self
+				.circuit_breaker_state
+				.get_or_create(&CircuitBreakerState {
+					server: breaker_state.server.clone(),
+					state: state.as_str().to_string(),
+				})
+				.set(value);
+		}
+	}
+}
+
+impl Recorder<ToolCacheHit, ()> for Metrics {
+	fn record(&self, hit: &ToolCacheHit, _: ()) {
+		self.tool_cache_hits.get_or_create(hit).inc();
+	}
+}
+
+impl Recorder<ToolCacheMiss, ()> for Metrics {
+	fn record(&self, miss: &ToolCacheMiss, _: ()) {
+		self.tool_cache_misses.get_or_create(miss).inc();
+	}
+}
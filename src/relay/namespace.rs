@@ -0,0 +1,340 @@
+use crate::inbound::{PrefixStrategy, RelayOptions};
+use rmcp::model::ResourceContents;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+pub(super) const DEFAULT_RESOURCE_SCHEME: &str = "mcp-proxy";
+
+/// ResourceNamespace rewrites upstream resource URIs into URIs naming their target,
+/// `<scheme>://<target>/<upstream uri>`, and maps them back.
+/// The upstream URI is kept verbatim, so resource templates still expand to URIs that map back.
+#[derive(Clone, Debug)]
+pub(super) struct ResourceNamespace {
+	scheme: String,
+}
+
+impl ResourceNamespace {
+	pub(super) fn new(scheme: Option<&str>) -> Self {
+		Self {
+			scheme: scheme.unwrap_or(DEFAULT_RESOURCE_SCHEME).to_string(),
+		}
+	}
+
+	pub(super) fn wrap(&self, target: &str, uri: &str) -> String {
+		format!("{}://{}/{}", self.scheme, target, uri)
+	}
+
+	/// Returns the target and upstream URI of a proxy URI, or None if it was not issued by this proxy.
+	pub(super) fn unwrap<'a>(&self, uri: &'a str) -> Option<(&'a str, &'a str)> {
+		let rest = uri
+			.strip_prefix(self.scheme.as_str())?
+			.strip_prefix("://")?;
+		match rest.split_once('/') {
+			Some((target, uri)) if !target.is_empty() && !uri.is_empty() => Some((target, uri)),
+			_ => None,
+		}
+	}
+
+	pub(super) fn wrap_contents(&self, target: &str, contents: &mut ResourceContents) {
+		let uri = match contents {
+			ResourceContents::TextResourceContents { uri, .. } => uri,
+			ResourceContents::BlobResourceContents { uri, .. } => uri,
+		};
+		*uri = self.wrap(target, uri);
+	}
+}
+
+/// Naming assigns the names tools and prompts are listed under, and maps them back to their target.
+#[derive(Clone, Debug)]
+pub(super) struct Naming {
+	separator: String,
+	prefix: PrefixStrategy,
+	max_length: Option<usize>,
+	sanitize: bool,
+}
+
+impl Naming {
+	pub(super) fn new(options: &RelayOptions) -> Self {
+		let separator = options.separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+		let max_length = match options.sanitize_names {
+			true => Some(
+				options
+					.max_name_length
+					.map_or(SANITIZED_MAX_LENGTH, |max| max.min(SANITIZED_MAX_LENGTH)),
+			),
+			false => options.max_name_length,
+		};
+		Self {
+			separator: match options.sanitize_names {
+				true => sanitize(separator),
+				false => separator.to_string(),
+			},
+			prefix: options.prefix.clone().unwrap_or_default(),
+			max_length,
+			sanitize: options.sanitize_names,
+		}
+	}
+
+	/// Names the items of every target, recording the names in the table.
+	/// The names only depend on the set of items, so listing the same items always gives the same names.
+	/// Items whose name is still taken after prefixing are left out, keeping the first one in the given order.
+	pub(super) fn assign(&self, table: &NameTable, items: &[(&str, &str)]) -> Vec<Option<String>> {
+		let mut targets_by_name: HashMap<&str, HashSet<&str>> = HashMap::new();
+		for (target, name) in items {
+			targets_by_name.entry(*name).or_default().insert(*target);
+		}
+		let mut taken = HashSet::new();
+		items
+			.iter()
+			.map(|(target, name)| {
+				let prefixed = match self.prefix {
+					PrefixStrategy::Always => true,
+					PrefixStrategy::OnConflict => targets_by_name[name].len() > 1,
+					PrefixStrategy::Never => false,
+				};
+				let exposed = match prefixed {
+					true => format!("{}{}{}", target, self.separator, name),
+					false => name.to_string(),
+				};
+				let exposed = self.shorten(exposed, target, name);
+				if !taken.insert(exposed.clone()) {
+					tracing::warn!(
+						"{}{}{} is hidden, its name {} is already taken",
+						target,
+						self.separator,
+						name,
+						exposed
+					);
+					return None;
+				}
+				table.insert(&exposed, target, name);
+				Some(exposed)
+			})
+			.collect()
+	}
+
+	/// Splits a name into its target and upstream name without a table.
+	/// This only works when names are always prefixed and the name cannot have been shortened.
+	/// As target names may contain the separator, the name is split after the first prefix naming a target.
+	pub(super) fn parse<'a>(
+		&self,
+		exposed: &'a str,
+		is_target: impl Fn(&str) -> bool,
+	) -> Option<(&'a str, &'a str)> {
+		if self.prefix != PrefixStrategy::Always
+			|| self.max_length.is_some_and(|max| exposed.len() >= max)
+		{
+			return None;
+		}
+		exposed
+			.match_indices(self.separator.as_str())
+			.map(|(i, separator)| (&exposed[..i], &exposed[i + separator.len()..]))
+			.find(|(target, name)| !name.is_empty() && is_target(target))
+	}
+
+	/// Returns whether the name of an item depends on the items of other targets,
+	/// in which case names can only be assigned over every page of every target.
+	pub(super) fn needs_every_page(&self) -> bool {
+		self.prefix != PrefixStrategy::Always
+	}
+
+	/// Shortens an over-long name, and sanitizes it if configured to.
+	/// Changed names end in a hash of the target and upstream name, so they stay unique.
+	/// They are mapped back through the name table, as the change cannot be undone from the name alone.
+	fn shorten(&self, exposed: String, target: &str, name: &str) -> String {
+		let sanitized = match self.sanitize {
+			true => sanitize(&exposed),
+			false => exposed.clone(),
+		};
+		let too_long = self.max_length.is_some_and(|max| exposed.len() > max);
+		if sanitized == exposed && !too_long {
+			return exposed;
+		}
+		let mut end = match self.max_length {
+			Some(max) => sanitized.len().min(max.saturating_sub(HASH_LENGTH + 1)),
+			None => sanitized.len(),
+		};
+		while !sanitized.is_char_boundary(end) {
+			end -= 1;
+		}
+		format!("{}_{:08x}", &sanitized[..end], hash(target, name) as u32)
+	}
+}
+
+const DEFAULT_SEPARATOR: &str = ":";
+const HASH_LENGTH: usize = 8;
+const SANITIZED_MAX_LENGTH: usize = 64;
+
+/// Replaces every character but ASCII letters, digits, `_` and `-` with `_`.
+fn sanitize(name: &str) -> String {
+	name
+		.chars()
+		.map(|c| match c {
+			'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+			_ => '_',
+		})
+		.collect()
+}
+
+/// FNV-1a, which unlike the std hasher is stable across releases.
+fn hash(target: &str, name: &str) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in target.bytes().chain([0]).chain(name.bytes()) {
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+/// NameTable maps the names handed out to a session back to their target and upstream name.
+#[derive(Clone, Debug, Default)]
+pub(super) struct NameTable(Arc<Mutex<HashMap<String, (String, String)>>>);
+
+impl NameTable {
+	fn insert(&self, exposed: &str, target: &str, name: &str) {
+		self
+			.0
+			.lock()
+			.unwrap()
+			.insert(exposed.to_string(), (target.to_string(), name.to_string()));
+	}
+
+	pub(super) fn get(&self, exposed: &str) -> Option<(String, String)> {
+		self.0.lock().unwrap().get(exposed).cloned()
+	}
+}
+
+#[test]
+fn test_resource_namespace() {
+	let namespace = ResourceNamespace::new(None);
+	let uri = namespace.wrap("files", "file:///tmp/a.txt");
+	assert_eq!(uri, "mcp-proxy://files/file:///tmp/a.txt");
+	assert_eq!(namespace.unwrap(&uri), Some(("files", "file:///tmp/a.txt")));
+	assert_eq!(namespace.unwrap("other://files/file:///tmp/a.txt"), None);
+	assert_eq!(namespace.unwrap("mcp-proxy://files/"), None);
+}
+
+#[test]
+fn test_assign() {
+	let naming = |prefix| {
+		Naming::new(&RelayOptions {
+			prefix: Some(prefix),
+			..Default::default()
+		})
+	};
+	let items = [("a", "echo"), ("b", "echo"), ("a", "add")];
+	let table = NameTable::default();
+	assert_eq!(
+		naming(PrefixStrategy::OnConflict).assign(&table, &items),
+		vec![
+			Some("a:echo".to_string()),
+			Some("b:echo".to_string()),
+			Some("add".to_string())
+		]
+	);
+	assert_eq!(
+		table.get("b:echo"),
+		Some(("b".to_string(), "echo".to_string()))
+	);
+	assert_eq!(
+		naming(PrefixStrategy::Never).assign(&table, &items),
+		vec![Some("echo".to_string()), None, Some("add".to_string())]
+	);
+	assert_eq!(
+		naming(PrefixStrategy::Always).assign(&table, &items)[2],
+		Some("a:add".to_string())
+	);
+}
+
+#[test]
+fn test_parse() {
+	let naming = Naming::new(&RelayOptions {
+		separator: Some("-".to_string()),
+		..Default::default()
+	});
+	let is_target = |target: &str| target == "my-server";
+	assert_eq!(
+		naming.parse("my-server-echo", is_target),
+		Some(("my-server", "echo"))
+	);
+	assert_eq!(naming.parse("other-echo", is_target), None);
+	assert_eq!(naming.parse("my-server-", is_target), None);
+	let on_conflict = Naming::new(&RelayOptions {
+		prefix: Some(PrefixStrategy::OnConflict),
+		..Default::default()
+	});
+	assert_eq!(on_conflict.parse("a:echo", |_| true), None);
+}
+
+#[test]
+fn test_shorten() {
+	let naming = Naming::new(&RelayOptions {
+		max_name_length: Some(20),
+		..Default::default()
+	});
+	assert_eq!(naming.shorten("a:echo".to_string(), "a", "echo"), "a:echo");
+	let long = naming.shorten(
+		"target:a_very_long_tool_name".to_string(),
+		"target",
+		"a_very_long_tool_name",
+	);
+	let other = naming.shorten(
+		"target:a_very_long_tool_name_2".to_string(),
+		"target",
+		"a_very_long_tool_name_2",
+	);
+	assert_eq!(long.len(), 20);
+	assert_ne!(long, other);
+	assert_eq!(naming.parse(&long, |_| true), None);
+}
+
+#[test]
+fn test_sanitize() {
+	let naming = Naming::new(&RelayOptions {
+		sanitize_names: true,
+		..Default::default()
+	});
+	let table = NameTable::default();
+	let items = [
+		("files", "read_file"),
+		("docs.v2", "read file"),
+		("docs.v2", "read.file"),
+	];
+	let names: Vec<String> = naming
+		.assign(&table, &items)
+		.into_iter()
+		.flatten()
+		.collect();
+	assert_eq!(names[0], "files_read_file");
+	assert!(names[1].starts_with("docs_v2_read_file_"));
+	assert!(names[2].starts_with("docs_v2_read_file_"));
+	assert_ne!(names[1], names[2]);
+	for name in &names {
+		assert!(name.len() <= 64);
+		assert!(
+			name
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+		);
+	}
+	assert_eq!(
+		table.get(&names[2]),
+		Some(("docs.v2".to_string(), "read.file".to_string()))
+	);
+
+	let long = "a".repeat(100);
+	assert_eq!(
+		naming.assign(&table, &[("t", &long)])[0]
+			.as_ref()
+			.unwrap()
+			.len(),
+		64
+	);
+
+	// Maximum lengths leaving no room for the hash are rejected
+	let options = |max: usize| {
+		serde_json::from_value::<RelayOptions>(serde_json::json!({ "max_name_length": max }))
+	};
+	assert!(options(9).is_err());
+	assert_eq!(options(10).unwrap().max_name_length, Some(10));
+}
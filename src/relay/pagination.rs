@@ -0,0 +1,90 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rmcp::model::{PaginatedRequestParam, PaginatedRequestParamInner};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The most pages listed from a single target when draining its pages.
+pub(super) const MAX_PAGES: usize = 100;
+
+/// Cursor is the cursor handed to clients for an aggregated list.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum Cursor {
+	/// The upstream cursor of each target that has more pages.
+	/// Targets that are not present have been fully listed.
+	Targets(BTreeMap<String, String>),
+	/// The offset into the merged list, when upstream pages are drained by the proxy.
+	Offset(usize),
+}
+
+impl Cursor {
+	pub(super) fn from_request(request: &PaginatedRequestParam) -> Result<Option<Self>, String> {
+		match request.as_ref().and_then(|r| r.cursor.as_ref()) {
+			Some(cursor) => Self::decode(cursor).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	pub(super) fn encode(&self) -> String {
+		// Serializing a map of strings or a number cannot fail
+		let json = serde_json::to_vec(self).unwrap();
+		URL_SAFE_NO_PAD.encode(json)
+	}
+
+	fn decode(cursor: &str) -> Result<Self, String> {
+		let json = URL_SAFE_NO_PAD
+			.decode(cursor)
+			.map_err(|e| format!("invalid cursor: {}", e))?;
+		serde_json::from_slice(&json).map_err(|e| format!("invalid cursor: {}", e))
+	}
+}
+
+/// Builds the request for one page of a target.
+pub(super) fn request(cursor: Option<String>) -> PaginatedRequestParam {
+	cursor.map(|cursor| PaginatedRequestParamInner {
+		cursor: Some(cursor),
+	})
+}
+
+/// Returns the page of a merged list starting at offset, and the cursor of the next page.
+pub(super) fn page<T>(
+	mut items: Vec<T>,
+	offset: usize,
+	page_size: Option<usize>,
+) -> (Vec<T>, Option<String>) {
+	let Some(page_size) = page_size.filter(|size| *size > 0) else {
+		return (items, None);
+	};
+	let offset = offset.min(items.len());
+	let end = offset.saturating_add(page_size).min(items.len());
+	let next = match end < items.len() {
+		true => Some(Cursor::Offset(end).encode()),
+		false => None,
+	};
+	items.truncate(end);
+	(items.split_off(offset), next)
+}
+
+#[test]
+fn test_cursor() {
+	let cursor = Cursor::Targets(BTreeMap::from([("a".to_string(), "next".to_string())]));
+	let with_cursor = request(Some(cursor.encode()));
+	assert_eq!(Cursor::from_request(&with_cursor), Ok(Some(cursor)));
+	assert_eq!(Cursor::from_request(&request(None)), Ok(None));
+	assert!(Cursor::from_request(&request(Some("not a cursor".to_string()))).is_err());
+}
+
+#[test]
+fn test_page() {
+	let (items, next) = page(vec![1, 2, 3, 4, 5], 0, Some(2));
+	assert_eq!(items, vec![1, 2]);
+	assert_eq!(next, Some(Cursor::Offset(2).encode()));
+	let (items, next) = page(vec![1, 2, 3, 4, 5], 4, Some(2));
+	assert_eq!(items, vec![5]);
+	assert_eq!(next, None);
+	let (items, next) = page(vec![1, 2, 3], 10, Some(2));
+	assert!(items.is_empty());
+	assert_eq!(next, None);
+	assert_eq!(page(vec![1, 2, 3], 0, None), (vec![1, 2, 3], None));
+}
@@ -0,0 +1,220 @@
+use rmcp::model::{CallToolRequestParam, ErrorData, JsonObject, Tool};
+use serde_json::{Value, json};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub(super) const SEARCH_TOOLS: &str = "search_tools";
+pub(super) const INVOKE_TOOL: &str = "invoke_tool";
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+// BM25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Returns the meta-tools listed instead of the tools of the targets in tool search mode.
+pub(super) fn meta_tools() -> Vec<Tool> {
+	vec![
+		tool(
+			SEARCH_TOOLS,
+			"Searches the available tools by keywords, returning the name, description and input schema of the best matches.",
+			json!({
+				"type": "object",
+				"properties": {
+					"query": {
+						"type": "string",
+						"description": "Keywords describing what the tool should do."
+					},
+					"limit": {
+						"type": "integer",
+						"description": "The most tools to return.",
+						"minimum": 1,
+						"maximum": MAX_LIMIT,
+						"default": DEFAULT_LIMIT
+					}
+				},
+				"required": ["query"]
+			}),
+		),
+		tool(
+			INVOKE_TOOL,
+			"Calls a tool found with search_tools.",
+			json!({
+				"type": "object",
+				"properties": {
+					"name": {
+						"type": "string",
+						"description": "The name of the tool, as returned by search_tools."
+					},
+					"arguments": {
+						"type": "object",
+						"description": "The arguments of the tool, following its input schema."
+					}
+				},
+				"required": ["name"]
+			}),
+		),
+	]
+}
+
+fn tool(name: &'static str, description: &'static str, schema: Value) -> Tool {
+	let Value::Object(schema) = schema else {
+		unreachable!("meta-tool schemas are objects");
+	};
+	Tool {
+		name: Cow::Borrowed(name),
+		description: Some(Cow::Borrowed(description)),
+		input_schema: Arc::new(schema),
+	}
+}
+
+/// Returns the query and limit of a search_tools call.
+pub(super) fn search_request(arguments: Option<&JsonObject>) -> Result<(String, usize), ErrorData> {
+	let query = arguments
+		.and_then(|a| a.get("query"))
+		.and_then(Value::as_str)
+		.ok_or_else(|| ErrorData::invalid_params("query must be a string", None))?;
+	let limit = arguments
+		.and_then(|a| a.get("limit"))
+		.and_then(Value::as_u64)
+		.map(|limit| (limit as usize).clamp(1, MAX_LIMIT))
+		.unwrap_or(DEFAULT_LIMIT);
+	Ok((query.to_string(), limit))
+}
+
+/// Maps an invoke_tool call to the call of the tool it names.
+pub(super) fn invocation(arguments: Option<JsonObject>) -> Result<CallToolRequestParam, ErrorData> {
+	let mut arguments = arguments.unwrap_or_default();
+	let name = match arguments.remove("name") {
+		Some(Value::String(name)) => name,
+		_ => return Err(ErrorData::invalid_params("name must be a string", None)),
+	};
+	let arguments = match arguments.remove("arguments") {
+		Some(Value::Object(arguments)) => Some(arguments),
+		None | Some(Value::Null) => None,
+		Some(_) => {
+			return Err(ErrorData::invalid_params(
+				"arguments must be an object",
+				None,
+			));
+		},
+	};
+	Ok(CallToolRequestParam {
+		name: Cow::Owned(name),
+		arguments,
+	})
+}
+
+/// Ranks tools against a query with BM25, over their name, description and input schema.
+/// Tools matching none of the query terms are left out.
+pub(super) fn rank(tools: Vec<Tool>, query: &str, limit: usize) -> Vec<Tool> {
+	let query = tokenize(query);
+	if query.is_empty() {
+		return vec![];
+	}
+	let documents: Vec<Vec<String>> = tools.iter().map(document).collect();
+	let count = documents.len() as f64;
+	let average_length = documents.iter().map(Vec::len).sum::<usize>() as f64 / count.max(1.0);
+	let mut frequencies: HashMap<&str, usize> = HashMap::new();
+	for document in &documents {
+		let mut seen: Vec<&str> = document.iter().map(String::as_str).collect();
+		seen.sort_unstable();
+		seen.dedup();
+		for term in seen {
+			*frequencies.entry(term).or_default() += 1;
+		}
+	}
+
+	let mut scored: Vec<(f64, Tool)> = tools
+		.into_iter()
+		.zip(&documents)
+		.map(|(tool, document)| {
+			let length = document.len() as f64;
+			let score = query
+				.iter()
+				.map(|term| {
+					let tf = document.iter().filter(|t| *t == term).count() as f64;
+					if tf == 0.0 {
+						return 0.0;
+					}
+					let df = frequencies.get(term.as_str()).copied().unwrap_or_default() as f64;
+					let idf = ((count - df + 0.5) / (df + 0.5) + 1.0).ln();
+					idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / average_length.max(1.0)))
+				})
+				.sum::<f64>();
+			(score, tool)
+		})
+		.filter(|(score, _)| *score > 0.0)
+		.collect();
+	scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+	scored
+		.into_iter()
+		.take(limit)
+		.map(|(_, tool)| tool)
+		.collect()
+}
+
+/// Returns the terms of a tool. Name terms are counted twice, as they say the most about a tool.
+fn document(tool: &Tool) -> Vec<String> {
+	let mut terms = tokenize(&tool.name);
+	terms.extend(tokenize(&tool.name));
+	if let Some(description) = &tool.description {
+		terms.extend(tokenize(description));
+	}
+	if let Some(Value::Object(properties)) = tool.input_schema.get("properties") {
+		for (name, property) in properties {
+			terms.extend(tokenize(name));
+			if let Some(description) = property.get("description").and_then(Value::as_str) {
+				terms.extend(tokenize(description));
+			}
+		}
+	}
+	terms
+}
+
+/// Splits text into lowercase words, breaking on punctuation, underscores and camelCase.
+fn tokenize(text: &str) -> Vec<String> {
+	let mut terms = Vec::new();
+	let mut current = String::new();
+	let mut previous_lowercase = false;
+	for c in text.chars() {
+		if !c.is_alphanumeric() {
+			if !current.is_empty() {
+				terms.push(std::mem::take(&mut current));
+			}
+			previous_lowercase = false;
+			continue;
+		}
+		if c.is_uppercase() && previous_lowercase && !current.is_empty() {
+			terms.push(std::mem::take(&mut current));
+		}
+		previous_lowercase = c.is_lowercase() || c.is_numeric();
+		current.extend(c.to_lowercase());
+	}
+	if !current.is_empty() {
+		terms.push(current);
+	}
+	terms
+}
+
+#[test]
+fn test_rank() {
+	let tools = vec![
+		tool(
+			"github:create_issue",
+			"Creates an issue in a repository",
+			json!({"type": "object"}),
+		),
+		tool(
+			"weather:getForecast",
+			"Returns the weather forecast for a city",
+			json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+		),
+		tool("echo", "Echoes its input", json!({"type": "object"})),
+	];
+	let ranked = rank(tools, "weather forecast", 10);
+	assert_eq!(ranked.len(), 1);
+	assert_eq!(ranked[0].name, "weather:getForecast");
+}
@@ -0,0 +1,403 @@
+use super::{UpstreamError, transform};
+use crate::outbound::{PromptRole, StaticPrompt, StaticResource, StaticSource};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use itertools::Itertools;
+use rmcp::model::{
+	AnnotateAble, ErrorData, GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage,
+	PromptMessageRole, RawResource, ReadResourceResult, Resource, ResourceContents,
+};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+/// StaticHandler serves the prompts and resources of a static target from its config and local files.
+#[derive(Debug)]
+pub(super) struct StaticHandler {
+	pub(super) prompts: Vec<StaticPrompt>,
+	pub(super) resources: Vec<StaticResource>,
+}
+
+impl StaticHandler {
+	pub(super) fn list_prompts(&self) -> Vec<Prompt> {
+		self
+			.prompts
+			.iter()
+			.map(|prompt| Prompt {
+				name: prompt.name.clone(),
+				description: prompt.description.clone(),
+				arguments: Some(
+					prompt
+						.arguments
+						.iter()
+						.map(|argument| PromptArgument {
+							name: argument.name.clone(),
+							description: argument.description.clone(),
+							required: Some(argument.required),
+						})
+						.collect(),
+				),
+			})
+			.collect()
+	}
+
+	/// Renders the messages of a prompt, replacing the `{{argument}}` references with the arguments.
+	/// Optional arguments that are not given render as empty strings.
+	pub(super) fn get_prompt(
+		&self,
+		name: &str,
+		arguments: Option<JsonObject>,
+	) -> Result<GetPromptResult, UpstreamError> {
+		let prompt = self
+			.prompts
+			.iter()
+			.find(|p| p.name == name)
+			.ok_or_else(|| mcp_error(ErrorData::invalid_params("unknown prompt", None)))?;
+		let arguments = arguments.unwrap_or_default();
+		if let Some(missing) = prompt
+			.arguments
+			.iter()
+			.find(|a| a.required && !arguments.contains_key(&a.name))
+		{
+			return Err(mcp_error(ErrorData::invalid_params(
+				format!("missing argument {}", missing.name),
+				None,
+			)));
+		}
+		let resolve = |name: &str| -> Result<Value, String> {
+			match arguments.get(name) {
+				Some(value) => Ok(value.clone()),
+				None if prompt.arguments.iter().any(|a| a.name == name) => Ok(Value::String(String::new())),
+				None => Err(format!("unknown argument {}", name)),
+			}
+		};
+		let messages = prompt
+			.messages
+			.iter()
+			.map(|message| {
+				let text = match transform::render_template(&Value::String(message.text.clone()), &resolve)
+				{
+					Ok(Value::String(text)) => text,
+					Ok(value) => value.to_string(),
+					Err(e) => return Err(mcp_error(ErrorData::invalid_params(e, None))),
+				};
+				let role = match message.role {
+					PromptRole::User => PromptMessageRole::User,
+					PromptRole::Assistant => PromptMessageRole::Assistant,
+				};
+				Ok(PromptMessage::new_text(role, text))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(GetPromptResult {
+			description: prompt.description.clone(),
+			messages,
+		})
+	}
+
+	/// Lists the resources, with one resource per file of each directory.
+	pub(super) async fn list_resources(&self) -> Result<Vec<Resource>, UpstreamError> {
+		let mut resources = Vec::new();
+		for resource in &self.resources {
+			match &resource.source {
+				StaticSource::Text(_) | StaticSource::File(_) => {
+					let mime_type = match &resource.source {
+						StaticSource::File(file) => resource
+							.mime_type
+							.clone()
+							.or_else(|| mime_type_of(Path::new(file), None)),
+						_ => resource
+							.mime_type
+							.clone()
+							.or_else(|| Some("text/plain".to_string())),
+					};
+					resources.push(describe(
+						&resource.uri,
+						resource.name.as_deref().unwrap_or(&resource.uri),
+						resource.description.clone(),
+						mime_type,
+					));
+				},
+				StaticSource::Directory(directory) => {
+					for path in walk(Path::new(directory)).await.map_err(io_error)? {
+						let segments = relative_segments(Path::new(directory), &path);
+						let uri = segments.iter().map(|s| encode_segment(s)).join("/");
+						resources.push(describe(
+							&join_uri(&resource.uri, &uri),
+							&segments.join("/"),
+							resource.description.clone(),
+							resource
+								.mime_type
+								.clone()
+								.or_else(|| mime_type_of(&path, None)),
+						));
+					}
+				},
+			}
+		}
+		Ok(resources)
+	}
+
+	pub(super) async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, UpstreamError> {
+		for resource in &self.resources {
+			let path = match &resource.source {
+				StaticSource::Text(text) if resource.uri == uri => {
+					return Ok(ReadResourceResult {
+						contents: vec![ResourceContents::TextResourceContents {
+							uri: uri.to_string(),
+							mime_type: resource
+								.mime_type
+								.clone()
+								.or_else(|| Some("text/plain".to_string())),
+							text: text.clone(),
+						}],
+					});
+				},
+				StaticSource::File(file) if resource.uri == uri => PathBuf::from(file),
+				StaticSource::Directory(directory) => {
+					let Some(relative) = strip_uri(&resource.uri, uri).and_then(decode_path) else {
+						continue;
+					};
+					match resolve_path(Path::new(directory), &relative) {
+						Some(path) => path,
+						None => continue,
+					}
+				},
+				_ => continue,
+			};
+			let bytes = tokio::fs::read(&path).await.map_err(io_error)?;
+			let mime_type = resource
+				.mime_type
+				.clone()
+				.or_else(|| mime_type_of(&path, Some(&bytes)));
+			let contents = match mime_type.as_deref().is_some_and(is_text) {
+				true => ResourceContents::TextResourceContents {
+					uri: uri.to_string(),
+					mime_type,
+					text: String::from_utf8_lossy(&bytes).into_owned(),
+				},
+				false => ResourceContents::BlobResourceContents {
+					uri: uri.to_string(),
+					mime_type,
+					blob: STANDARD.encode(&bytes),
+				},
+			};
+			return Ok(ReadResourceResult {
+				contents: vec![contents],
+			});
+		}
+		Err(mcp_error(ErrorData::resource_not_found(
+			"unknown resource uri",
+			None,
+		)))
+	}
+}
+
+fn describe(
+	uri: &str,
+	name: &str,
+	description: Option<String>,
+	mime_type: Option<String>,
+) -> Resource {
+	let mut resource = RawResource::new(uri, name);
+	resource.description = description;
+	resource.mime_type = mime_type;
+	resource.no_annotation()
+}
+
+/// Returns every file under a directory, sorted so the resources are listed in a stable order.
+async fn walk(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+	let mut files = Vec::new();
+	let mut directories = vec![directory.to_path_buf()];
+	while let Some(directory) = directories.pop() {
+		let mut entries = tokio::fs::read_dir(&directory).await?;
+		while let Some(entry) = entries.next_entry().await? {
+			let file_type = entry.file_type().await?;
+			if file_type.is_dir() {
+				directories.push(entry.path());
+			} else if file_type.is_file() {
+				files.push(entry.path());
+			}
+		}
+	}
+	files.sort();
+	Ok(files)
+}
+
+fn relative_segments(directory: &Path, path: &Path) -> Vec<String> {
+	path
+		.strip_prefix(directory)
+		.unwrap_or(path)
+		.components()
+		.map(|c| c.as_os_str().to_string_lossy().into_owned())
+		.collect()
+}
+
+/// Percent-encodes a path segment, keeping only the characters URIs never reserve.
+fn encode_segment(segment: &str) -> String {
+	let mut encoded = String::with_capacity(segment.len());
+	for byte in segment.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+				encoded.push(byte as char)
+			},
+			_ => encoded.push_str(&format!("%{:02X}", byte)),
+		}
+	}
+	encoded
+}
+
+/// Decodes the percent-encoded segments of a relative path.
+/// Segments decoding to a `/` are rejected, as they would name another path.
+fn decode_path(path: &str) -> Option<String> {
+	let segments = path
+		.split('/')
+		.map(|segment| {
+			let mut bytes = Vec::with_capacity(segment.len());
+			let mut rest = segment.as_bytes();
+			while let Some((&byte, tail)) = rest.split_first() {
+				if byte == b'%' {
+					let hex = tail
+						.get(..2)
+						.filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+					bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+					rest = &tail[2..];
+				} else {
+					bytes.push(byte);
+					rest = tail;
+				}
+			}
+			String::from_utf8(bytes).ok().filter(|s| !s.contains('/'))
+		})
+		.collect::<Option<Vec<_>>>()?;
+	Some(segments.join("/"))
+}
+
+/// Joins the URI prefix of a directory and the relative path of a file, such as `docs://` and `guide.md`.
+fn join_uri(prefix: &str, relative: &str) -> String {
+	match prefix.ends_with('/') {
+		true => format!("{}{}", prefix, relative),
+		false => format!("{}/{}", prefix, relative),
+	}
+}
+
+fn strip_uri<'a>(prefix: &str, uri: &'a str) -> Option<&'a str> {
+	let relative = uri.strip_prefix(prefix)?;
+	let relative = match prefix.ends_with('/') {
+		true => relative,
+		false => relative.strip_prefix('/')?,
+	};
+	Some(relative).filter(|relative| !relative.is_empty())
+}
+
+/// Maps the relative path of a URI to a file of the directory.
+/// Paths escaping the directory, through `..` or symlinks, are rejected.
+fn resolve_path(directory: &Path, relative: &str) -> Option<PathBuf> {
+	let relative = Path::new(relative);
+	if !relative
+		.components()
+		.all(|c| matches!(c, Component::Normal(_)))
+	{
+		return None;
+	}
+	let directory = directory.canonicalize().ok()?;
+	let path = directory.join(relative).canonicalize().ok()?;
+	path.starts_with(&directory).then_some(path)
+}
+
+/// Detects the MIME type of a file from its extension, or from its contents when they are known.
+fn mime_type_of(path: &Path, contents: Option<&[u8]>) -> Option<String> {
+	let extension = path
+		.extension()
+		.map(|e| e.to_string_lossy().to_lowercase())
+		.unwrap_or_default();
+	let mime_type = match extension.as_str() {
+		"txt" | "text" | "log" => "text/plain",
+		"md" | "markdown" => "text/markdown",
+		"html" | "htm" => "text/html",
+		"css" => "text/css",
+		"csv" => "text/csv",
+		"js" | "mjs" => "text/javascript",
+		"json" => "application/json",
+		"yaml" | "yml" => "application/yaml",
+		"toml" => "application/toml",
+		"xml" => "application/xml",
+		"sh" => "application/x-sh",
+		"py" => "text/x-python",
+		"rs" => "text/x-rust",
+		"go" => "text/x-go",
+		"pdf" => "application/pdf",
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"webp" => "image/webp",
+		"svg" => "image/svg+xml",
+		_ => match contents {
+			Some(contents) if std::str::from_utf8(contents).is_ok() => "text/plain",
+			Some(_) => "application/octet-stream",
+			None => return None,
+		},
+	};
+	Some(mime_type.to_string())
+}
+
+/// Returns whether contents of a MIME type are served as text rather than base64.
+fn is_text(mime_type: &str) -> bool {
+	mime_type.starts_with("text/")
+		|| matches!(
+			mime_type,
+			"application/json"
+				| "application/yaml"
+				| "application/toml"
+				| "application/xml"
+				| "application/x-sh"
+				| "image/svg+xml"
+		)
+}
+
+fn mcp_error(e: ErrorData) -> UpstreamError {
+	UpstreamError::ServiceError(rmcp::ServiceError::McpError(e))
+}
+
+fn io_error(e: std::io::Error) -> UpstreamError {
+	mcp_error(ErrorData::internal_error(e.to_string(), None))
+}
+
+#[test]
+fn test_resolve_path() {
+	let directory = std::env::temp_dir().join("static-target-test");
+	std::fs::create_dir_all(directory.join("docs")).unwrap();
+	std::fs::write(directory.join("docs/guide.md"), "# Guide").unwrap();
+
+	assert_eq!(strip_uri("docs://", "docs://guide.md"), Some("guide.md"));
+	assert_eq!(
+		strip_uri("file:///docs", "file:///docs/guide.md"),
+		Some("guide.md")
+	);
+	assert_eq!(strip_uri("file:///docs", "file:///docsguide.md"), None);
+	assert_eq!(
+		resolve_path(&directory, "docs/guide.md"),
+		directory.join("docs/guide.md").canonicalize().ok()
+	);
+	assert_eq!(
+		resolve_path(&directory.join("docs"), "../docs/guide.md"),
+		None
+	);
+	assert_eq!(resolve_path(&directory, "/etc/passwd"), None);
+}
+
+#[test]
+fn test_encode_segment() {
+	for name in [
+		"release notes #2?.md",
+		"100%.txt",
+		"plain-name_1.txt",
+		"résumé.pdf",
+	] {
+		let encoded = encode_segment(name);
+		assert!(!encoded.contains([' ', '#', '?']));
+		assert_eq!(decode_path(&encoded).as_deref(), Some(name));
+	}
+	assert_eq!(decode_path("docs/a%20b.md").as_deref(), Some("docs/a b.md"));
+	assert_eq!(decode_path("a%2Fb"), None);
+	assert_eq!(decode_path("a%2"), None);
+	assert_eq!(decode_path("a%+1"), None);
+}
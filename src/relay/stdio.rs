@@ -0,0 +1,165 @@
+use crate::outbound::StdioLimits;
+use crate::relay::health::LogBuffer;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, watch};
+use tracing::Level;
+
+/// Stderr configures where the stderr output of a target goes.
+pub(super) struct Stderr {
+	pub target: String,
+	pub level: Level,
+	pub buffer: Arc<LogBuffer>,
+}
+
+/// Process is a spawned stdio target.
+/// The child is killed when this is dropped, along with its process group if one was requested.
+#[derive(Debug)]
+pub(super) struct Process {
+	pid: Option<u32>,
+	process_group: Option<i32>,
+	exit: watch::Receiver<Option<String>>,
+	// Dropping this stops the waiter task, which kills the child.
+	_shutdown: oneshot::Sender<()>,
+}
+
+impl Process {
+	pub(super) fn spawn(
+		command: &mut Command,
+		limits: Option<&StdioLimits>,
+		stderr: Stderr,
+	) -> std::io::Result<(Self, (ChildStdout, ChildStdin))> {
+		command
+			.kill_on_drop(true)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped());
+		let kill_process_group = limits.is_some_and(|l| l.kill_process_group);
+		if kill_process_group {
+			command.process_group(0);
+		}
+		if let Some(limits) = limits.cloned() {
+			// SAFETY: setrlimit is async-signal-safe and the closure does not allocate.
+			unsafe {
+				command.pre_exec(move || apply_limits(&limits));
+			}
+		}
+
+		let mut child = command.spawn()?;
+		let stdin = child
+			.stdin
+			.take()
+			.ok_or_else(|| std::io::Error::other("stdin was taken"))?;
+		let stdout = child
+			.stdout
+			.take()
+			.ok_or_else(|| std::io::Error::other("stdout was taken"))?;
+		let pid = child.id();
+		if let Some(output) = child.stderr.take() {
+			tokio::spawn(log_stderr(output, pid, stderr));
+		}
+		// With process_group(0), the group id is the pid of the child.
+		let process_group = match kill_process_group {
+			true => pid.map(|pid| pid as i32),
+			false => None,
+		};
+
+		let (exit_tx, exit) = watch::channel(None);
+		let (shutdown, mut shutdown_rx) = oneshot::channel::<()>();
+		tokio::spawn(async move {
+			tokio::select! {
+				status = child.wait() => {
+					let reason = match status {
+						Ok(status) => format!("process exited: {}", status),
+						Err(e) => format!("error waiting for process: {}", e),
+					};
+					let _ = exit_tx.send(Some(reason));
+				},
+				_ = &mut shutdown_rx => {
+					// The child is killed by kill_on_drop when it goes out of scope.
+				},
+			}
+		});
+
+		Ok((
+			Self {
+				pid,
+				process_group,
+				exit,
+				_shutdown: shutdown,
+			},
+			(stdout, stdin),
+		))
+	}
+
+	pub(super) fn id(&self) -> Option<u32> {
+		self.pid
+	}
+
+	/// Returns the reason the process exited, if it has.
+	pub(super) fn exited(&self) -> Option<String> {
+		self.exit.borrow().clone()
+	}
+}
+
+impl Drop for Process {
+	fn drop(&mut self) {
+		if let Some(pgid) = self.process_group {
+			// SAFETY: killpg has no memory safety requirements.
+			unsafe {
+				libc::killpg(pgid, libc::SIGKILL);
+			}
+		}
+		// The child itself is killed by kill_on_drop.
+	}
+}
+
+async fn log_stderr(output: ChildStderr, pid: Option<u32>, stderr: Stderr) {
+	let Stderr {
+		target,
+		level,
+		buffer,
+	} = stderr;
+	let mut lines = BufReader::new(output).lines();
+	loop {
+		let line = match lines.next_line().await {
+			Ok(Some(line)) => line,
+			Ok(None) => return,
+			Err(e) => {
+				tracing::debug!(target_name = %target, ?pid, "error reading stderr: {}", e);
+				return;
+			},
+		};
+		match level {
+			Level::ERROR => tracing::error!(target_name = %target, ?pid, "{}", line),
+			Level::WARN => tracing::warn!(target_name = %target, ?pid, "{}", line),
+			Level::INFO => tracing::info!(target_name = %target, ?pid, "{}", line),
+			Level::DEBUG => tracing::debug!(target_name = %target, ?pid, "{}", line),
+			_ => tracing::trace!(target_name = %target, ?pid, "{}", line),
+		}
+		buffer.push(pid, line);
+	}
+}
+
+fn apply_limits(limits: &StdioLimits) -> std::io::Result<()> {
+	let set = |resource, value: Option<u64>| -> std::io::Result<()> {
+		let Some(value) = value else {
+			return Ok(());
+		};
+		let limit = libc::rlimit {
+			rlim_cur: value as libc::rlim_t,
+			rlim_max: value as libc::rlim_t,
+		};
+		// SAFETY: limit is a valid rlimit for the duration of the call.
+		match unsafe { libc::setrlimit(resource, &limit) } {
+			0 => Ok(()),
+			_ => Err(std::io::Error::last_os_error()),
+		}
+	};
+	set(libc::RLIMIT_AS, limits.memory_bytes)?;
+	set(libc::RLIMIT_CPU, limits.cpu_seconds)?;
+	set(libc::RLIMIT_NOFILE, limits.open_files)?;
+	Ok(())
+}
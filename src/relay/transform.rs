@@ -0,0 +1,272 @@
+use crate::outbound::ToolOverride;
+use crate::rbac::Identity;
+use rmcp::model::{JsonObject, Tool};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Applies the tool overrides of a target to the tools it lists.
+/// Renamed tools are listed under their new name, followed by one copy per alias.
+pub(super) fn apply_tool_overrides(overrides: &[ToolOverride], tools: Vec<Tool>) -> Vec<Tool> {
+	let mut result = Vec::with_capacity(tools.len());
+	for tool in tools {
+		let Some(o) = overrides.iter().find(|o| o.name == tool.name) else {
+			result.push(tool);
+			continue;
+		};
+		if o.hide {
+			continue;
+		}
+		let input_schema = match &o.input_schema_patch {
+			Some(patch) => {
+				let mut schema = Value::Object(tool.input_schema.as_ref().clone());
+				merge_patch(&mut schema, &Value::Object(patch.clone()));
+				match schema {
+					Value::Object(schema) => schema,
+					_ => JsonObject::new(),
+				}
+			},
+			None => tool.input_schema.as_ref().clone(),
+		};
+		let input_schema = Arc::new(hide_arguments(o, input_schema));
+		let tool = Tool {
+			name: Cow::Owned(o.rename.clone().unwrap_or_else(|| o.name.clone())),
+			description: o.description.clone().map(Cow::Owned).or(tool.description),
+			input_schema,
+		};
+		let aliases = o
+			.aliases
+			.iter()
+			.map(|alias| Tool {
+				name: Cow::Owned(alias.clone()),
+				..tool.clone()
+			})
+			.collect::<Vec<_>>();
+		result.push(tool);
+		result.extend(aliases);
+	}
+	result
+}
+
+/// Maps the name a tool is listed under back to its name on the target.
+/// Returns None for hidden tools, and for the original name of renamed tools.
+pub(super) fn upstream_tool_name<'a>(
+	overrides: &'a [ToolOverride],
+	name: &'a str,
+) -> Option<&'a str> {
+	let renamed = overrides.iter().find(|o| {
+		!o.hide && (o.rename.as_deref() == Some(name) || o.aliases.iter().any(|a| a == name))
+	});
+	if let Some(o) = renamed {
+		return Some(&o.name);
+	}
+	match overrides.iter().find(|o| o.name == name) {
+		Some(o) if o.hide || o.rename.is_some() => None,
+		_ => Some(name),
+	}
+}
+
+/// Removes fixed arguments from an input schema, and stops requiring arguments with a default.
+fn hide_arguments(o: &ToolOverride, mut schema: JsonObject) -> JsonObject {
+	if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+		for name in o.fixed_arguments.keys() {
+			properties.remove(name);
+		}
+		for (name, value) in &o.default_arguments {
+			if let Some(Value::Object(property)) = properties.get_mut(name) {
+				property.insert("default".to_string(), value.clone());
+			}
+		}
+	}
+	if let Some(Value::Array(required)) = schema.get_mut("required") {
+		required.retain(|name| match name.as_str() {
+			Some(name) => {
+				!o.fixed_arguments.contains_key(name) && !o.default_arguments.contains_key(name)
+			},
+			None => true,
+		});
+	}
+	schema
+}
+
+/// Fills in the default arguments of a tool call and overrides its fixed arguments.
+/// Fixed string arguments may reference the claims of the caller as `{{claim}}`.
+pub(super) fn inject_arguments(
+	o: &ToolOverride,
+	arguments: Option<JsonObject>,
+	id: &Identity,
+) -> Result<Option<JsonObject>, String> {
+	if o.default_arguments.is_empty() && o.fixed_arguments.is_empty() {
+		return Ok(arguments);
+	}
+	let mut arguments = arguments.unwrap_or_default();
+	for (name, value) in &o.default_arguments {
+		if !arguments.contains_key(name) {
+			arguments.insert(name.clone(), value.clone());
+		}
+	}
+	for (name, value) in &o.fixed_arguments {
+		arguments.insert(name.clone(), render(value, id)?);
+	}
+	Ok(Some(arguments))
+}
+
+/// Renders the claim references of a fixed argument.
+fn render(value: &Value, id: &Identity) -> Result<Value, String> {
+	render_template(value, &|name| {
+		id.claim(name)
+			.cloned()
+			.ok_or_else(|| format!("missing claim {} for a fixed argument", name))
+	})
+}
+
+/// Renders the `{{reference}}`s of a string, resolving each with resolve.
+/// A string that is a single reference takes the resolved value as is, so non-string values keep their type.
+pub(super) fn render_template(
+	value: &Value,
+	resolve: &dyn Fn(&str) -> Result<Value, String>,
+) -> Result<Value, String> {
+	let Value::String(template) = value else {
+		return Ok(value.clone());
+	};
+	let reference = |name: &str| resolve(name.trim());
+	if let Some(name) = template
+		.strip_prefix("{{")
+		.and_then(|t| t.strip_suffix("}}"))
+		.filter(|name| !name.contains("{{"))
+	{
+		return reference(name);
+	}
+	let mut rendered = String::new();
+	let mut rest = template.as_str();
+	while let Some(start) = rest.find("{{") {
+		let end = rest[start..]
+			.find("}}")
+			.ok_or_else(|| format!("unterminated reference in {}", template))?;
+		rendered.push_str(&rest[..start]);
+		match reference(&rest[start + 2..start + end])? {
+			Value::String(s) => rendered.push_str(&s),
+			other => rendered.push_str(&other.to_string()),
+		}
+		rest = &rest[start + end + 2..];
+	}
+	rendered.push_str(rest);
+	Ok(Value::String(rendered))
+}
+
+/// Applies a JSON merge patch (RFC 7386).
+fn merge_patch(target: &mut Value, patch: &Value) {
+	let Value::Object(patch) = patch else {
+		*target = patch.clone();
+		return;
+	};
+	if !target.is_object() {
+		*target = Value::Object(JsonObject::new());
+	}
+	let Value::Object(target) = target else {
+		return;
+	};
+	for (key, value) in patch {
+		match value {
+			Value::Null => {
+				target.remove(key);
+			},
+			value => merge_patch(target.entry(key.clone()).or_insert(Value::Null), value),
+		}
+	}
+}
+
+#[test]
+fn test_apply_tool_overrides() {
+	let tool = |name: &'static str| Tool {
+		name: Cow::Borrowed(name),
+		description: None,
+		input_schema: Arc::new(JsonObject::new()),
+	};
+	let tools = vec![tool("search"), tool("delete"), tool("echo")];
+	let overrides = vec![
+		ToolOverride {
+			rename: Some("find".to_string()),
+			aliases: vec!["lookup".to_string()],
+			description: Some("Finds things".to_string()),
+			..tool_override("search")
+		},
+		ToolOverride {
+			hide: true,
+			..tool_override("delete")
+		},
+	];
+	let listed = apply_tool_overrides(&overrides, tools);
+	let names: Vec<&str> = listed.iter().map(|t| t.name.as_ref()).collect();
+	assert_eq!(names, vec!["find", "lookup", "echo"]);
+	assert_eq!(listed[0].description.as_deref(), Some("Finds things"));
+	assert_eq!(upstream_tool_name(&overrides, "find"), Some("search"));
+	assert_eq!(upstream_tool_name(&overrides, "lookup"), Some("search"));
+	assert_eq!(upstream_tool_name(&overrides, "search"), None);
+	assert_eq!(upstream_tool_name(&overrides, "delete"), None);
+	assert_eq!(upstream_tool_name(&overrides, "echo"), Some("echo"));
+}
+
+#[cfg(test)]
+fn tool_override(name: &str) -> ToolOverride {
+	ToolOverride {
+		name: name.to_string(),
+		rename: None,
+		aliases: vec![],
+		hide: false,
+		description: None,
+		input_schema_patch: None,
+		default_arguments: JsonObject::new(),
+		fixed_arguments: JsonObject::new(),
+		cache: None,
+	}
+}
+
+#[test]
+fn test_inject_arguments() {
+	let object = |value: Value| value.as_object().cloned().unwrap_or_default();
+	let o = ToolOverride {
+		default_arguments: object(serde_json::json!({"limit": 10})),
+		fixed_arguments: object(serde_json::json!({"owner": "{{sub}}", "org": "acme"})),
+		..tool_override("search")
+	};
+	let id = Identity::new(Some(object(serde_json::json!({"sub": "alice"}))), None);
+	let arguments = inject_arguments(
+		&o,
+		Some(object(
+			serde_json::json!({"owner": "mallory", "query": "x"}),
+		)),
+		&id,
+	);
+	assert_eq!(
+		arguments.map(|a| a.map(Value::Object)),
+		Ok(Some(serde_json::json!({
+			"owner": "alice",
+			"org": "acme",
+			"query": "x",
+			"limit": 10
+		})))
+	);
+	assert!(inject_arguments(&o, None, &Identity::empty()).is_err());
+
+	let schema = object(serde_json::json!({
+		"type": "object",
+		"properties": {
+			"owner": {"type": "string"},
+			"limit": {"type": "integer"},
+			"query": {"type": "string"}
+		},
+		"required": ["owner", "limit", "query"]
+	}));
+	assert_eq!(
+		Value::Object(hide_arguments(&o, schema)),
+		serde_json::json!({
+			"type": "object",
+			"properties": {
+				"limit": {"type": "integer", "default": 10},
+				"query": {"type": "string"}
+			},
+			"required": ["query"]
+		})
+	);
+}
@@ -0,0 +1,112 @@
+use jsonschema::Validator;
+use rmcp::model::{ErrorData, JsonObject};
+use serde_json::{Value, json};
+use std::sync::{Arc, OnceLock};
+
+/// ToolSchema is the input schema a tool was listed with, compiled on the first call to validate.
+pub(super) struct ToolSchema {
+	schema: Arc<JsonObject>,
+	// None if the schema is invalid
+	validator: OnceLock<Option<Validator>>,
+}
+
+impl ToolSchema {
+	pub(super) fn new(schema: Arc<JsonObject>) -> Self {
+		Self {
+			schema,
+			validator: OnceLock::new(),
+		}
+	}
+
+	/// Returns whether this is the given schema, so a tool listed again keeps its compiled validator.
+	pub(super) fn is(&self, schema: &JsonObject) -> bool {
+		*self.schema == *schema
+	}
+
+	/// Validates the arguments of a tool call against the schema.
+	/// The error lists each failing path, so the client can fix the call.
+	pub(super) fn validate(&self, arguments: Option<&JsonObject>) -> Result<(), ErrorData> {
+		let validator = self.validator.get_or_init(|| {
+			jsonschema::validator_for(&Value::Object((*self.schema).clone()))
+				// The upstream owns the schema, so an invalid one should not block its tool
+				.inspect_err(|e| tracing::debug!("skipping validation of an invalid input schema: {}", e))
+				.ok()
+		});
+		let Some(validator) = validator else {
+			return Ok(());
+		};
+		let arguments = Value::Object(arguments.cloned().unwrap_or_default());
+		let errors: Vec<Value> = validator
+			.iter_errors(&arguments)
+			.map(|e| {
+				json!({
+					"path": e.instance_path.to_string(),
+					"message": e.to_string(),
+				})
+			})
+			.collect();
+		if errors.is_empty() {
+			return Ok(());
+		}
+		Err(ErrorData::invalid_params(
+			format!("invalid arguments: {}", summarize(&errors)),
+			Some(json!({ "errors": errors })),
+		))
+	}
+}
+
+fn summarize(errors: &[Value]) -> String {
+	errors
+		.iter()
+		.map(|e| {
+			let path = e["path"].as_str().unwrap_or_default();
+			let message = e["message"].as_str().unwrap_or_default();
+			match path {
+				"" => message.to_string(),
+				path => format!("{}: {}", path, message),
+			}
+		})
+		.collect::<Vec<_>>()
+		.join("; ")
+}
+
+#[test]
+fn test_validate() {
+	let schema = json!({
+		"type": "object",
+		"properties": {
+			"name": { "type": "string" },
+			"count": { "type": "integer" },
+		},
+		"required": ["name"],
+	});
+	let schema = ToolSchema::new(Arc::new(schema.as_object().cloned().unwrap()));
+	let arguments = |value: Value| value.as_object().cloned();
+
+	assert!(
+		schema
+			.validate(arguments(json!({ "name": "a", "count": 1 })).as_ref())
+			.is_ok()
+	);
+	let error = schema
+		.validate(arguments(json!({ "count": "one" })).as_ref())
+		.unwrap_err();
+	assert_eq!(error.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+	let mut paths: Vec<&str> = error.data.as_ref().unwrap()["errors"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|e| e["path"].as_str().unwrap())
+		.collect();
+	paths.sort();
+	assert_eq!(paths, vec!["", "/count"]);
+
+	// Invalid schemas are not enforced
+	let invalid = ToolSchema::new(Arc::new(
+		json!({ "type": "not a type" })
+			.as_object()
+			.cloned()
+			.unwrap(),
+	));
+	assert!(invalid.validate(None).is_ok());
+}
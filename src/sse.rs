@@ -173,14 +173,12 @@ async fn sse_handler(
 		tokio::spawn(async move {
 			let stream = ReceiverStream::new(from_client_rx);
 			let sink = PollSender::new(to_client_tx).sink_map_err(std::io::Error::other);
-			let result = serve_server(
-				Relay::new(app.state.clone(), claims, app.metrics.clone()),
-				(sink, stream),
-			)
-			.await
-			.inspect_err(|e| {
-				tracing::error!("serving error: {:?}", e);
-			});
+			let relay = Relay::new(app.state.clone(), claims, app.metrics.clone());
+			let result = serve_server(relay.clone(), (sink, stream))
+				.await
+				.inspect_err(|e| {
+					tracing::error!("serving error: {:?}", e);
+				});
 
 			if let Err(e) = result {
 				tracing::error!(error = ?e, "initialize error");
@@ -190,6 +188,7 @@ async fn sse_handler(
 			let _running_result = result.unwrap().waiting().await.inspect_err(|e| {
 				tracing::error!(error = ?e, "running error");
 			});
+			relay.close().await;
 			app.txs.write().await.remove(&session);
 		});
 	}
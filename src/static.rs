@@ -34,6 +34,7 @@ pub async fn run_local_client(
 		let mut state = state_clone.write().unwrap();
 		state.targets.clear();
 		state.policies.clear();
+		state.upstreams.clear();
 		let num_targets = cfg.targets.len();
 		let num_policies = cfg.policies.len();
 		for target in cfg.targets.clone() {
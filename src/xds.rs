@@ -12,10 +12,16 @@ pub use types::*;
 
 use xds::mcp::kgateway_dev::rbac::Config as XdsRbac;
 use xds::mcp::kgateway_dev::target::Target as XdsTarget;
+use xds::mcp::kgateway_dev::target::target::Filter as XdsFilter;
 use xds::mcp::kgateway_dev::target::target::Target as XdsTargetSpec;
+use xds::mcp::kgateway_dev::target::target::sse_target::LoadBalancing as XdsLoadBalancing;
+use xds::mcp::kgateway_dev::target::target::static_target::prompt::message::Role as XdsPromptRole;
+use xds::mcp::kgateway_dev::target::target::static_target::resource::Source as XdsResourceSource;
 
 use self::envoy::service::discovery::v3::DeltaDiscoveryRequest;
 use crate::rbac;
+use crate::relay::cache::ToolCache;
+use crate::relay::health::UpstreamStore;
 use crate::strng::Strng;
 use crate::xds;
 use std::collections::HashMap;
@@ -120,6 +126,8 @@ impl ProxyStateUpdateMutator {
     )]
 	pub fn remove_target(&self, state: &mut XdsStore, xds_name: &Strng) {
 		state.targets.remove(xds_name);
+		state.upstreams.remove(xds_name);
+		state.tool_cache.remove(xds_name);
 	}
 
 	#[instrument(
@@ -180,6 +188,10 @@ pub enum ParseError {
 	MissingFields,
 	#[error("invalid schema")]
 	InvalidSchema,
+	#[error("invalid log level")]
+	InvalidLogLevel,
+	#[error("invalid duration")]
+	InvalidDuration,
 }
 
 impl TryFrom<&XdsTarget> for outbound::Target {
@@ -192,11 +204,45 @@ impl TryFrom<&XdsTarget> for outbound::Target {
 				port: sse.port,
 				path: sse.path.clone(),
 				backend_auth: None,
+				endpoints: sse
+					.endpoints
+					.iter()
+					.map(|e| outbound::Endpoint {
+						host: e.host.clone(),
+						port: e.port,
+					})
+					.collect(),
+				dns_discovery: sse.dns_discovery,
+				load_balancing: match sse.load_balancing() {
+					XdsLoadBalancing::RoundRobin => outbound::LoadBalancing::RoundRobin,
+					XdsLoadBalancing::LeastConnections => outbound::LoadBalancing::LeastConnections,
+				},
 			},
 			XdsTargetSpec::Stdio(stdio) => outbound::TargetSpec::Stdio {
 				cmd: stdio.cmd.clone(),
 				args: stdio.args.clone(),
 				env: stdio.env.clone(),
+				cwd: match stdio.cwd.as_str() {
+					"" => None,
+					cwd => Some(cwd.to_string()),
+				},
+				clear_env: stdio.clear_env,
+				inherit_env: stdio.inherit_env.clone(),
+				limits: stdio.limits.as_ref().map(|limits| outbound::StdioLimits {
+					memory_bytes: Some(limits.memory_bytes).filter(|v| *v > 0),
+					cpu_seconds: Some(limits.cpu_seconds).filter(|v| *v > 0),
+					open_files: Some(limits.open_files).filter(|v| *v > 0),
+					kill_process_group: limits.kill_process_group,
+				}),
+				stderr_level: match stdio.stderr_level.as_str() {
+					"" => None,
+					level => {
+						level
+							.parse::<tracing::Level>()
+							.map_err(|_| ParseError::InvalidLogLevel)?;
+						Some(level.to_string())
+					},
+				},
 			},
 			XdsTargetSpec::Openapi(openapi) => outbound::TargetSpec::OpenAPI {
 				host: openapi.host.clone(),
@@ -208,17 +254,227 @@ impl TryFrom<&XdsTarget> for outbound::Target {
 					schema
 				},
 			},
+			XdsTargetSpec::Composite(composite) => outbound::TargetSpec::Composite {
+				tools: composite
+					.tools
+					.iter()
+					.map(|tool| {
+						Ok(outbound::CompositeTool {
+							name: tool.name.clone(),
+							description: Some(tool.description.clone()).filter(|d| !d.is_empty()),
+							input_schema: match &tool.input_schema {
+								Some(schema) => object(schema)?,
+								None => serde_json::Map::from_iter([(
+									"type".to_string(),
+									serde_json::Value::String("object".to_string()),
+								)]),
+							},
+							steps: tool
+								.steps
+								.iter()
+								.map(|step| {
+									Ok(outbound::CompositeStep {
+										id: step.id.clone(),
+										target: step.target.clone(),
+										tool: step.tool.clone(),
+										arguments: match &step.arguments {
+											Some(arguments) => object(arguments)?,
+											None => serde_json::Map::new(),
+										},
+									})
+								})
+								.collect::<Result<Vec<_>, ParseError>>()?,
+							output: match &tool.output {
+								Some(output) => {
+									Some(serde_json::to_value(output).map_err(|_| ParseError::InvalidSchema)?)
+								},
+								None => None,
+							},
+						})
+					})
+					.collect::<Result<Vec<_>, ParseError>>()?,
+			},
+			XdsTargetSpec::Static(s) => outbound::TargetSpec::Static {
+				prompts: s
+					.prompts
+					.iter()
+					.map(|prompt| outbound::StaticPrompt {
+						name: prompt.name.clone(),
+						description: Some(prompt.description.clone()).filter(|d| !d.is_empty()),
+						arguments: prompt
+							.arguments
+							.iter()
+							.map(|argument| outbound::StaticPromptArgument {
+								name: argument.name.clone(),
+								description: Some(argument.description.clone()).filter(|d| !d.is_empty()),
+								required: argument.required,
+							})
+							.collect(),
+						messages: prompt
+							.messages
+							.iter()
+							.map(|message| outbound::StaticPromptMessage {
+								role: match message.role() {
+									XdsPromptRole::User => outbound::PromptRole::User,
+									XdsPromptRole::Assistant => outbound::PromptRole::Assistant,
+								},
+								text: message.text.clone(),
+							})
+							.collect(),
+					})
+					.collect(),
+				resources: s
+					.resources
+					.iter()
+					.map(|resource| {
+						Ok(outbound::StaticResource {
+							uri: resource.uri.clone(),
+							name: Some(resource.name.clone()).filter(|n| !n.is_empty()),
+							description: Some(resource.description.clone()).filter(|d| !d.is_empty()),
+							mime_type: Some(resource.mime_type.clone()).filter(|m| !m.is_empty()),
+							source: match resource.source.as_ref().ok_or(ParseError::MissingFields)? {
+								XdsResourceSource::Text(text) => outbound::StaticSource::Text(text.clone()),
+								XdsResourceSource::File(file) => outbound::StaticSource::File(file.clone()),
+								XdsResourceSource::Directory(directory) => {
+									outbound::StaticSource::Directory(directory.clone())
+								},
+							},
+						})
+					})
+					.collect::<Result<Vec<_>, ParseError>>()?,
+			},
+		};
+		let timeouts = match &value.timeouts {
+			Some(timeouts) => outbound::Timeouts {
+				connect: parse_duration(&timeouts.connect)?,
+				list: parse_duration(&timeouts.list)?,
+				call: parse_duration(&timeouts.call)?,
+			},
+			None => outbound::Timeouts::default(),
+		};
+		let retries = match &value.retries {
+			Some(retries) if retries.attempts > 1 => Some(outbound::RetryPolicy {
+				attempts: retries.attempts,
+				backoff: parse_duration(&retries.backoff)?.unwrap_or_default(),
+			}),
+			_ => None,
 		};
+		let circuit_breaker = match &value.circuit_breaker {
+			Some(breaker) if breaker.consecutive_failures > 0 => Some(outbound::CircuitBreaker {
+				consecutive_failures: breaker.consecutive_failures,
+				open_interval: parse_duration(&breaker.open_interval)?.unwrap_or(DEFAULT_OPEN_INTERVAL),
+			}),
+			_ => None,
+		};
+		let tools = value
+			.tools
+			.iter()
+			.map(|tool| {
+				Ok(outbound::ToolOverride {
+					name: tool.name.clone(),
+					rename: Some(tool.rename.clone()).filter(|r| !r.is_empty()),
+					aliases: tool.aliases.clone(),
+					hide: tool.hide,
+					description: Some(tool.description.clone()).filter(|d| !d.is_empty()),
+					input_schema_patch: match &tool.input_schema_patch {
+						Some(patch) => Some(object(patch)?),
+						None => None,
+					},
+					default_arguments: arguments(&tool.default_arguments)?,
+					fixed_arguments: arguments(&tool.fixed_arguments)?,
+					cache: match &tool.cache {
+						Some(cache) => Some(outbound::ResponseCache {
+							ttl: parse_duration(&cache.ttl)?.ok_or(ParseError::MissingFields)?,
+							max_entries: match cache.max_entries {
+								0 => DEFAULT_CACHE_ENTRIES,
+								n => n as usize,
+							},
+							per_identity: cache.per_identity,
+						}),
+						None => None,
+					},
+				})
+			})
+			.collect::<Result<Vec<_>, ParseError>>()?;
 		Ok(outbound::Target {
 			name: value.name.clone(),
 			spec,
+			timeouts,
+			retries,
+			circuit_breaker,
+			tools,
+			filters: value
+				.filters
+				.as_ref()
+				.map(|filters| outbound::Filters {
+					tools: filters.tools.as_ref().map(filter).unwrap_or_default(),
+					prompts: filters.prompts.as_ref().map(filter).unwrap_or_default(),
+					resources: filters.resources.as_ref().map(filter).unwrap_or_default(),
+				})
+				.unwrap_or_default(),
+			min_log_level: match value.min_log_level.as_str() {
+				"" => None,
+				level => Some(
+					serde_json::from_value(serde_json::Value::String(level.to_string()))
+						.map_err(|_| ParseError::InvalidLogLevel)?,
+				),
+			},
+			list_cache_ttl: parse_duration(&value.list_cache_ttl)?,
 		})
 	}
 }
 
+fn object(
+	value: &pbjson_types::Struct,
+) -> Result<serde_json::Map<String, serde_json::Value>, ParseError> {
+	match serde_json::to_value(value) {
+		Ok(serde_json::Value::Object(object)) => Ok(object),
+		_ => Err(ParseError::InvalidSchema),
+	}
+}
+
+fn arguments(
+	arguments: &HashMap<String, pbjson_types::Value>,
+) -> Result<serde_json::Map<String, serde_json::Value>, ParseError> {
+	arguments
+		.iter()
+		.map(|(name, value)| {
+			let value = serde_json::to_value(value).map_err(|_| ParseError::InvalidSchema)?;
+			Ok((name.clone(), value))
+		})
+		.collect()
+}
+
+fn filter(filter: &XdsFilter) -> outbound::Filter {
+	outbound::Filter {
+		include: filter.include.clone(),
+		exclude: filter.exclude.clone(),
+	}
+}
+
+const DEFAULT_CACHE_ENTRIES: usize = 100;
+
+const DEFAULT_OPEN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn parse_duration(
+	duration: &Option<pbjson_types::Duration>,
+) -> Result<Option<std::time::Duration>, ParseError> {
+	duration
+		.clone()
+		.map(|d| std::time::Duration::try_from(d).map_err(|_| ParseError::InvalidDuration))
+		.transpose()
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TargetStore {
 	by_name: HashMap<String, outbound::Target>,
+	// Changes each time a target is inserted, so anything derived from an older config can be dropped
+	#[serde(skip)]
+	generations: HashMap<String, u64>,
+	#[serde(skip)]
+	next_generation: u64,
+	#[serde(skip)]
+	updated: Arc<tokio::sync::Notify>,
 }
 
 impl Default for TargetStore {
@@ -231,16 +487,36 @@ impl TargetStore {
 	pub fn new() -> Self {
 		Self {
 			by_name: HashMap::new(),
+			generations: HashMap::new(),
+			next_generation: 0,
+			updated: Arc::new(tokio::sync::Notify::new()),
 		}
 	}
 
 	pub fn remove(&mut self, name: &str) {
 		// TODO: Drain connections from target
 		self.by_name.remove(name);
+		self.generations.remove(name);
 	}
 
 	pub fn insert(&mut self, target: outbound::Target) {
+		self.next_generation += 1;
+		self
+			.generations
+			.insert(target.name.clone(), self.next_generation);
 		self.by_name.insert(target.name.clone(), target);
+		self.updated.notify_one();
+	}
+
+	/// Returns what is notified when a target is inserted or updated.
+	/// A notification is kept until it is waited for, so one waiter misses no update.
+	pub fn updated(&self) -> Arc<tokio::sync::Notify> {
+		self.updated.clone()
+	}
+
+	/// Returns the generation of the config of a target, which changes whenever the target is updated.
+	pub fn generation(&self, name: &str) -> u64 {
+		self.generations.get(name).copied().unwrap_or_default()
 	}
 
 	pub fn get(&self, name: &str) -> Option<&outbound::Target> {
@@ -256,6 +532,7 @@ impl TargetStore {
 
 	pub fn clear(&mut self) {
 		self.by_name.clear();
+		self.generations.clear();
 	}
 }
 
@@ -302,6 +579,8 @@ impl PolicyStore {
 pub struct XdsStore {
 	pub targets: TargetStore,
 	pub policies: PolicyStore,
+	pub upstreams: UpstreamStore,
+	pub tool_cache: ToolCache,
 	pub listener: inbound::Listener,
 }
 
@@ -310,6 +589,8 @@ impl XdsStore {
 		Self {
 			targets: TargetStore::new(),
 			policies: PolicyStore::new(),
+			upstreams: UpstreamStore::new(),
+			tool_cache: ToolCache::default(),
 			listener,
 		}
 	}